@@ -1,4 +1,6 @@
 use super::tlv;
+use super::face::FaceTable;
+use super::pipeline::scope;
 use super::socket::UdpPacket;
 use std::sync::Arc;
 
@@ -11,13 +13,14 @@ pub fn thread(
     chan_in: Arc<Injector<Arc<UdpPacket>>>,
     chan_mgmt: Arc<Injector<Arc<UdpPacket>>>,
     chans_out: Vec<Arc<Injector<Arc<UdpPacket>>>>,
+    faces: Arc<FaceTable>,
 ) {
     std::thread::spawn(move || {
         loop {
             let steal = chan_in.steal();
             match steal {
                 crossbeam::deque::Steal::Success(packet) => {
-                    dispatch_udp(packet, &chan_mgmt, &chans_out);
+                    dispatch_udp(packet, &chan_mgmt, &chans_out, &faces);
                 }
                 crossbeam::deque::Steal::Empty => {
                     std::thread::sleep(std::time::Duration::from_millis(1));
@@ -31,7 +34,8 @@ pub fn thread(
 fn dispatch_udp(
     packet: Arc<UdpPacket>,
     chan_mgmt: &Arc<Injector<Arc<UdpPacket>>>,
-    chans_out: &Vec<Arc<Injector<Arc<UdpPacket>>>>,
+    chans_out: &[Arc<Injector<Arc<UdpPacket>>>],
+    faces: &FaceTable,
 ) {
     let res = tlv::vec_decode::read_tlo(&packet.data[..]);
     match res {
@@ -66,27 +70,18 @@ fn dispatch_udp(
                         }
                     }
                     if is_mgmt {
+                        // Management is only reachable from local faces
+                        let name = &packet.data[o..o+name_tlo.l as usize];
+                        if !scope::can_receive(name, faces.scope(packet.face)) {
+                            println!("Scope violation, dropping management packet from face {}", packet.face);
+                            return;
+                        }
                         chan_mgmt.push(packet);
                         return;
                     }
                 }
 
-                if name_tlo.l > 32  { // hackkkkkkkk
-                    // flood to all pipelines
-                    println!("Flood to all pipelines {}", name_tlo.l);
-                    for chan in chans_out {
-                        chan.push(packet.clone());
-                    }
-                    return;
-                }
-
-                // Hash the name
-                // TODO: drop segment number in this hashing
-                let mut hash = 0;
-                for b in &packet.data[o..o+name_tlo.l as usize] {
-                    hash += *b as u64;
-                }
-                let idx = (hash % chans_out.len() as u64) as usize;
+                let idx = pipeline_index(&packet.data[o..o+name_tlo.l as usize], chans_out.len());
                 chans_out[idx].push(packet);
             } else {
                 println!("dispatch: unknown TLV type, dropping {:?}", tlo.t);
@@ -96,4 +91,79 @@ fn dispatch_udp(
             println!("Error decoding packet: {:?}", e);
         }
     }
-}
\ No newline at end of file
+}
+
+
+/**
+ * Pick a pipeline by the first name component
+ * Names under a common first component share a pipeline, so CanBePrefix
+ * Interests meet their Data in the same PIT and Content Store
+ */
+fn pipeline_index(name: &[u8], pipelines: usize) -> usize {
+    let first = match tlv::vec_decode::read_tlo(name) {
+        Ok(tlo) if tlo.o + tlo.l as usize <= name.len() => &name[..tlo.o + tlo.l as usize],
+        _ => name,
+    };
+    (fasthash::metro::hash64(first) % pipelines as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face::INTERNAL_FACE_ID;
+    use crate::table::cs::{CSEntry, ContentStore};
+
+    fn packet(t: tlv::Type, components: &[&str]) -> Arc<UdpPacket> {
+        let name: Vec<u8> = components.iter().flat_map(|c| [&[8, c.len() as u8][..], c.as_bytes()].concat()).collect();
+        let mut data = vec![t as u8, name.len() as u8 + 2, tlv::Type::Name as u8, name.len() as u8];
+        data.extend_from_slice(&name);
+        Arc::new(UdpPacket { data, face: INTERNAL_FACE_ID, lp: None, lost: false })
+    }
+
+    #[test]
+    fn prefix_shares_pipeline() {
+        let faces = FaceTable::new();
+        let mgmt = Arc::new(Injector::new());
+        let pipelines: Vec<_> = (0..8).map(|_| Arc::new(Injector::new())).collect();
+
+        for first in ["a", "ndn", "example", "0123456789abcdef0123456789abcdef"] {
+            dispatch_udp(packet(tlv::Type::Interest, &[first]), &mgmt, &pipelines, &faces);
+            dispatch_udp(packet(tlv::Type::Data, &[first, "b", "segment-0000000001"]), &mgmt, &pipelines, &faces);
+
+            // Interest and Data reach exactly one pipeline, the same one
+            let received: Vec<usize> = pipelines.iter()
+                .map(|p| std::iter::from_fn(|| p.steal().success()).count())
+                .collect();
+            assert_eq!(received.iter().sum::<usize>(), 2);
+            assert!(received.contains(&2));
+        }
+        assert!(mgmt.is_empty());
+    }
+
+    #[test]
+    fn prefix_finds_cached_data() {
+        let faces = FaceTable::new();
+        let mgmt = Arc::new(Injector::new());
+        let pipelines: Vec<_> = (0..8).map(|_| Arc::new(Injector::new())).collect();
+        let mut stores: Vec<_> = (0..8).map(|_| ContentStore::new(16)).collect();
+
+        // Each pipeline caches the Data it receives
+        for first in ["a", "ndn", "example"] {
+            dispatch_udp(packet(tlv::Type::Data, &[first, "b", "c"]), &mgmt, &pipelines, &faces);
+        }
+        for (pipeline, cs) in pipelines.iter().zip(stores.iter_mut()) {
+            while let Some(data) = pipeline.steal().success() {
+                let name = &data.data[4..];
+                cs.insert(name, CSEntry::new(data.data.clone(), 1000));
+            }
+        }
+
+        // A CanBePrefix Interest reaches the pipeline holding its Data
+        for first in ["a", "ndn", "example"] {
+            let interest = packet(tlv::Type::Interest, &[first]);
+            dispatch_udp(interest.clone(), &mgmt, &pipelines, &faces);
+            let idx = pipelines.iter().position(|p| p.steal().is_success()).unwrap();
+            assert!(stores[idx].find(&interest.data[4..], true, false).is_some());
+        }
+    }
+}
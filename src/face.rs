@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+pub type FaceId = u64;
+
+// Reserved FaceIds, as in NFD
+pub const INTERNAL_FACE_ID: FaceId = 1;
+const FIRST_FACE_ID: FaceId = 256;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FaceScope {
+    Local,
+    NonLocal,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FacePersistency {
+    OnDemand,
+    Persistent,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LinkType {
+    PointToPoint,
+}
+
+/**
+ * A face is a link to a peer or a local application
+ * Faces are shared by all threads, sending only queues the packet
+ */
+pub trait Face: Send + Sync {
+    fn id(&self) -> FaceId;
+    fn remote_uri(&self) -> String;
+    fn local_uri(&self) -> String;
+    fn scope(&self) -> FaceScope;
+    fn persistency(&self) -> FacePersistency;
+    fn link_type(&self) -> LinkType;
+    fn mtu(&self) -> usize;
+    fn send(&self, packet: Vec<u8>);
+
+    fn reliability(&self) -> bool {
+        false
+    }
+
+    /**
+     * Enable or disable NDNLPv2 link reliability
+     * Returns false if the face does not support it
+     */
+    fn set_reliability(&self, _enabled: bool) -> bool {
+        false
+    }
+}
+
+/**
+ * A channel creates faces to remote URIs of its protocol
+ */
+pub trait Channel: Send + Sync {
+    /**
+     * Canonical form of a remote URI, if the channel handles its scheme
+     */
+    fn canonize(&self, remote_uri: &str) -> Option<String>;
+
+    /**
+     * Create a face to a remote URI, if the channel can reach it
+     */
+    fn connect(&self, id: FaceId, remote_uri: &str, persistency: FacePersistency) -> Option<Arc<dyn Face>>;
+}
+
+struct FaceTableInner {
+    faces: HashMap<FaceId, Arc<dyn Face>>,
+    by_remote: HashMap<String, FaceId>,
+    next_id: FaceId,
+}
+
+/**
+ * Table of all faces, keyed by FaceId and by remote URI
+ */
+pub struct FaceTable {
+    inner: RwLock<FaceTableInner>,
+    channels: RwLock<Vec<Arc<dyn Channel>>>,
+}
+
+impl FaceTable {
+    pub fn new() -> FaceTable {
+        FaceTable {
+            inner: RwLock::new(FaceTableInner {
+                faces: HashMap::new(),
+                by_remote: HashMap::new(),
+                next_id: FIRST_FACE_ID,
+            }),
+            channels: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn add_channel(&self, channel: Arc<dyn Channel>) {
+        self.channels.write().unwrap().push(channel);
+    }
+
+    pub fn get(&self, id: FaceId) -> Option<Arc<dyn Face>> {
+        self.inner.read().unwrap().faces.get(&id).cloned()
+    }
+
+    pub fn find_remote(&self, remote_uri: &str) -> Option<FaceId> {
+        self.inner.read().unwrap().by_remote.get(remote_uri).copied()
+    }
+
+    /**
+     * Get the face to a remote URI, or add one made by make
+     */
+    pub fn get_or_add(&self, remote_uri: &str, make: impl FnOnce(FaceId) -> Arc<dyn Face>) -> FaceId {
+        if let Some(id) = self.find_remote(remote_uri) {
+            return id;
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        if let Some(id) = inner.by_remote.get(remote_uri) {
+            return *id;
+        }
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let face = make(id);
+        inner.by_remote.insert(face.remote_uri(), id);
+        inner.faces.insert(id, face);
+        println!("Created face {} to {}", id, remote_uri);
+        id
+    }
+
+    /**
+     * Get the face to a remote URI, creating it through a channel
+     * The URI is canonized first, so e.g. udp:// and udp4:// find the same face
+     */
+    pub fn connect(&self, remote_uri: &str, persistency: FacePersistency) -> Option<FaceId> {
+        let channels = self.channels.read().unwrap().clone();
+        let remote_uri = &channels.iter().find_map(|c| c.canonize(remote_uri))?;
+        if let Some(id) = self.find_remote(remote_uri) {
+            return Some(id);
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        if let Some(id) = inner.by_remote.get(remote_uri) {
+            return Some(*id);
+        }
+
+        let id = inner.next_id;
+        let face = channels.iter().find_map(|c| c.connect(id, remote_uri, persistency))?;
+        inner.next_id += 1;
+        inner.by_remote.insert(face.remote_uri(), id);
+        inner.faces.insert(id, face);
+        println!("Created face {} to {}", id, remote_uri);
+        Some(id)
+    }
+
+    pub fn remove(&self, id: FaceId) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(face) = inner.faces.remove(&id) {
+            inner.by_remote.remove(&face.remote_uri());
+            println!("Removed face {} to {}", id, face.remote_uri());
+        }
+    }
+
+    pub fn list(&self) -> Vec<Arc<dyn Face>> {
+        self.inner.read().unwrap().faces.values().cloned().collect()
+    }
+
+    /**
+     * Send a packet to a face, packets to unknown faces are dropped
+     */
+    pub fn send(&self, id: FaceId, packet: Vec<u8>) {
+        match self.get(id) {
+            Some(face) => face.send(packet),
+            None => println!("No face {}, dropping packet", id),
+        }
+    }
+
+    /**
+     * Scope of a face, the internal face is local and unknown faces are not
+     */
+    pub fn scope(&self, id: FaceId) -> FaceScope {
+        if id == INTERNAL_FACE_ID {
+            return FaceScope::Local;
+        }
+        self.get(id).map_or(FaceScope::NonLocal, |f| f.scope())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::UdpChannel;
+
+    #[test]
+    fn connect_canonizes_uri() {
+        let faces = FaceTable::new();
+        faces.add_channel(Arc::new(UdpChannel::new("127.0.0.1:7766")));
+
+        let id = faces.connect("udp://192.0.2.1:6363", FacePersistency::Persistent).unwrap();
+        assert_eq!(faces.connect("udp4://192.0.2.1:6363", FacePersistency::Persistent), Some(id));
+        assert_eq!(faces.get(id).unwrap().remote_uri(), "udp4://192.0.2.1:6363");
+        assert_eq!(faces.list().len(), 1);
+
+        assert_eq!(faces.connect("udp6://[2001:db8::1]:6363", FacePersistency::Persistent), None);
+        assert_eq!(faces.connect("ether://192.0.2.1", FacePersistency::Persistent), None);
+    }
+}
@@ -0,0 +1,645 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::face::FaceId;
+use crate::socket::UdpPacket;
+use crate::table::{now_ms, measurements::FaceInfo};
+use crate::tlv::{self, lp::LpPacket};
+
+// Default MTU of UDP faces, to avoid IP fragmentation
+pub const UDP_MTU: usize = 1400;
+
+// Incomplete packets are dropped after this time (ms)
+const REASSEMBLY_TIMEOUT: u64 = 500;
+
+// Maximum number of packets being reassembled at once, in total and per face
+const REASSEMBLY_MAX_PARTIAL: usize = 4096;
+const REASSEMBLY_MAX_PARTIAL_PER_FACE: usize = 64;
+
+// Smallest payload of a fragment other than the last one, which bounds FragCount
+const REASSEMBLY_MIN_FRAGMENT: usize = 64;
+const REASSEMBLY_MAX_FRAG_COUNT: usize = tlv::MAX_NDN_PACKET_SIZE.div_ceil(REASSEMBLY_MIN_FRAGMENT);
+
+// Space for the LpPacket and Fragment TLV type and length fields
+const FRAGMENT_OVERHEAD: usize = 2 * 4;
+
+// Retransmissions of a frame before it is considered lost
+const RELIABILITY_MAX_RETX: u32 = 3;
+
+// Retransmission timeout bounds before and after RTT samples (ms)
+const RELIABILITY_INITIAL_RTO: u64 = 1000;
+const RELIABILITY_MIN_RTO: u64 = 50;
+const RELIABILITY_MAX_RTO: u64 = 4000;
+
+// Acks piggybacked on one frame, and delay before sending them alone (ms)
+const RELIABILITY_MAX_ACKS: usize = 16;
+const RELIABILITY_ACK_DELAY: u64 = 5;
+
+// Peers without traffic for this long are forgotten (ms)
+const RELIABILITY_PEER_TIMEOUT: u64 = 60000;
+
+// Space for TxSequence and Ack fields, to be reserved from the MTU
+pub const RELIABILITY_OVERHEAD: usize = (1 + RELIABILITY_MAX_ACKS) * 12;
+
+/**
+ * Fragments of a packet received so far
+ */
+struct PartialPacket {
+    count: usize,
+    fragments: BTreeMap<usize, Vec<u8>>,
+    size: usize,
+    headers: Option<LpPacket>,
+    expiry: u64,
+}
+
+/**
+ * Receive side of the link service
+ * Unwraps LpPackets and reassembles fragmented packets
+ */
+pub struct Reassembler {
+    partial: HashMap<(FaceId, u64), PartialPacket>,
+    partial_per_face: HashMap<FaceId, usize>,
+    last_clean: u64,
+    pub timeouts: u64,
+    reliability: Option<Arc<Mutex<Reliability>>>,
+}
+
+impl Reassembler {
+    pub fn new(reliability: Option<Arc<Mutex<Reliability>>>) -> Reassembler {
+        Reassembler {
+            partial: HashMap::new(),
+            partial_per_face: HashMap::new(),
+            last_clean: now_ms(),
+            timeouts: 0,
+            reliability,
+        }
+    }
+
+    /**
+     * Unwrap a received frame into the network layer packet it carries
+     * Link headers of LpPackets are kept on the packet for the pipelines
+     * addr is the remote of datagram faces, needed for link reliability
+     * Returns None if the frame carries no complete network layer packet
+     */
+    pub fn receive(&mut self, data: &[u8], face: FaceId, addr: Option<SocketAddr>) -> Option<UdpPacket> {
+        self.clean();
+
+        let tlo = tlv::vec_decode::read_tlo(data).ok()?;
+        if tlo.t != tlv::Type::LpPacket as u64 {
+            return Some(UdpPacket { data: data.to_vec(), face, lp: None, lost: false });
+        }
+
+        let mut lp = match LpPacket::decode(data) {
+            Ok((lp, _)) => lp,
+            Err(e) => {
+                println!("link: invalid LpPacket from face {}, dropping: {}", face, e);
+                return None;
+            }
+        };
+
+        if let (Some(reliability), Some(addr)) = (&self.reliability, addr) {
+            reliability.lock().unwrap().receive(&mut lp, addr);
+        }
+
+        if lp.frag_count.is_some_and(|c| c > 1) {
+            return self.reassemble(lp, face);
+        }
+
+        // Packets without a fragment only carry link information
+        let fragment = lp.fragment.take()?;
+        Some(UdpPacket { data: fragment, face, lp: Some(lp), lost: false })
+    }
+
+    fn reassemble(&mut self, mut lp: LpPacket, face: FaceId) -> Option<UdpPacket> {
+        let count = lp.frag_count? as usize;
+        let index = lp.frag_index.unwrap_or(0) as usize;
+        let (sequence, fragment) = match (lp.sequence, lp.fragment.take()) {
+            (Some(sequence), Some(fragment)) if index < count => (sequence, fragment),
+            _ => {
+                println!("link: invalid fragment from face {}, dropping", face);
+                return None;
+            }
+        };
+        // Only the last fragment may be smaller than the minimum
+        if count > REASSEMBLY_MAX_FRAG_COUNT || (index + 1 < count && fragment.len() < REASSEMBLY_MIN_FRAGMENT) {
+            println!("link: invalid fragment size or count from face {}, dropping", face);
+            return None;
+        }
+
+        // Fragments of a packet have consecutive sequence numbers
+        let key = (face, sequence.wrapping_sub(index as u64));
+        if !self.partial.contains_key(&key) {
+            let per_face = self.partial_per_face.get(&face).copied().unwrap_or(0);
+            if self.partial.len() >= REASSEMBLY_MAX_PARTIAL || per_face >= REASSEMBLY_MAX_PARTIAL_PER_FACE {
+                println!("link: too many partial packets, dropping fragment from face {}", face);
+                return None;
+            }
+            self.partial_per_face.insert(face, per_face + 1);
+        }
+
+        let partial = self.partial.entry(key).or_insert_with(|| PartialPacket {
+            count,
+            fragments: BTreeMap::new(),
+            size: 0,
+            headers: None,
+            expiry: now_ms() + REASSEMBLY_TIMEOUT,
+        });
+        if partial.count != count || partial.fragments.contains_key(&index) {
+            return None;
+        }
+
+        partial.size += fragment.len();
+        if partial.size > tlv::MAX_NDN_PACKET_SIZE {
+            println!("link: reassembled packet from face {} too large, dropping", face);
+            self.remove_partial(&key);
+            return None;
+        }
+
+        // Link headers are carried by the first fragment
+        if index == 0 {
+            partial.headers = Some(lp);
+        }
+        partial.fragments.insert(index, fragment);
+        if partial.fragments.len() < count {
+            return None;
+        }
+
+        let partial = self.remove_partial(&key)?;
+        let mut data = Vec::with_capacity(partial.size);
+        for fragment in partial.fragments.into_values() {
+            data.extend_from_slice(&fragment);
+        }
+        Some(UdpPacket { data, face, lp: partial.headers, lost: false })
+    }
+
+    fn remove_partial(&mut self, key: &(FaceId, u64)) -> Option<PartialPacket> {
+        let partial = self.partial.remove(key)?;
+        if let Some(n) = self.partial_per_face.get_mut(&key.0) {
+            *n -= 1;
+            if *n == 0 {
+                self.partial_per_face.remove(&key.0);
+            }
+        }
+        Some(partial)
+    }
+
+    /**
+     * Drop packets whose fragments did not all arrive in time
+     */
+    fn clean(&mut self) {
+        let now = now_ms();
+        if now - self.last_clean < REASSEMBLY_TIMEOUT {
+            return;
+        }
+        self.last_clean = now;
+
+        let expired: Vec<_> = self.partial.iter()
+            .filter(|(_, p)| p.expiry <= now)
+            .map(|(k, _)| *k)
+            .collect();
+        for key in expired {
+            self.remove_partial(&key);
+            self.timeouts += 1;
+        }
+    }
+}
+
+/**
+ * Send side of the link service
+ * Splits packets larger than the MTU into LpPacket fragments
+ */
+pub struct Fragmenter {
+    mtu: usize,
+    next_sequence: u64,
+}
+
+/**
+ * Start sequences at an arbitrary number so restarts do not reuse them
+ */
+fn initial_sequence() -> u64 {
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    fasthash::metro::hash64(seed.to_be_bytes())
+}
+
+impl Fragmenter {
+    pub fn new(mtu: usize) -> Fragmenter {
+        Fragmenter {
+            mtu,
+            next_sequence: initial_sequence(),
+        }
+    }
+
+    /**
+     * Split an encoded packet into frames no larger than the MTU
+     */
+    pub fn fragment(&mut self, packet: Vec<u8>) -> Vec<Vec<u8>> {
+        if packet.len() <= self.mtu {
+            return vec![packet];
+        }
+
+        // Split the network layer packet, keeping link headers on the first fragment
+        let (mut headers, payload) = match LpPacket::decode(&packet) {
+            Ok((mut lp, _)) => match lp.fragment.take() {
+                Some(fragment) => (lp, fragment),
+                None => { return vec![packet]; }
+            },
+            Err(_) => (LpPacket::default(), packet),
+        };
+        headers.sequence = Some(0);
+        headers.frag_index = Some(0);
+        headers.frag_count = Some(0);
+
+        // Fragment headers take at most this space
+        let first_room = self.mtu.saturating_sub(headers.encode().len() + FRAGMENT_OVERHEAD);
+        let bare = LpPacket { sequence: Some(0), frag_index: Some(0), frag_count: Some(0), ..Default::default() };
+        let room = self.mtu.saturating_sub(bare.encode().len() + FRAGMENT_OVERHEAD);
+        if first_room < REASSEMBLY_MIN_FRAGMENT || room < REASSEMBLY_MIN_FRAGMENT {
+            println!("link: MTU {} too small to fragment, dropping", self.mtu);
+            return Vec::new();
+        }
+
+        let mut chunks = vec![&payload[..first_room.min(payload.len())]];
+        chunks.extend(payload[chunks[0].len()..].chunks(room));
+
+        let count = chunks.len() as u64;
+        let mut frames = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let mut lp = if i == 0 { headers.clone() } else { bare.clone() };
+            lp.sequence = Some(self.next_sequence);
+            lp.frag_index = Some(i as u64);
+            lp.frag_count = Some(count);
+            lp.fragment = Some(chunk.to_vec());
+            frames.push(lp.encode());
+            self.next_sequence = self.next_sequence.wrapping_add(1);
+        }
+        frames
+    }
+}
+
+/**
+ * Frame sent with a TxSequence and not acknowledged yet
+ */
+struct UnackedFrame {
+    lp: LpPacket,
+    sent_at: u64,
+    retx: u32,
+}
+
+/**
+ * Reliability state of the link to one peer
+ */
+struct Peer {
+    next_tx_sequence: u64,
+    unacked: HashMap<u64, UnackedFrame>,
+    acks: Vec<u64>,
+    acks_since: u64,
+    rtt: FaceInfo,
+    last_used: u64,
+}
+
+impl Peer {
+    fn rto(&self) -> u64 {
+        self.rtt.rto()
+            .map(|rto| rto.clamp(RELIABILITY_MIN_RTO, RELIABILITY_MAX_RTO))
+            .unwrap_or(RELIABILITY_INITIAL_RTO)
+    }
+
+    /**
+     * Assign a TxSequence to a frame and piggyback pending acks
+     */
+    fn transmit(&mut self, mut lp: LpPacket, now: u64, retx: u32) -> Vec<u8> {
+        let tx_sequence = self.next_tx_sequence;
+        self.next_tx_sequence = self.next_tx_sequence.wrapping_add(1);
+
+        lp.tx_sequence = Some(tx_sequence);
+        lp.acks = self.take_acks();
+        let frame = lp.encode();
+
+        lp.tx_sequence = None;
+        lp.acks.clear();
+        self.unacked.insert(tx_sequence, UnackedFrame { lp, sent_at: now, retx });
+        self.last_used = now;
+        frame
+    }
+
+    fn take_acks(&mut self) -> Vec<u64> {
+        let n = self.acks.len().min(RELIABILITY_MAX_ACKS);
+        self.acks.drain(..n).collect()
+    }
+}
+
+/**
+ * Network layer packet sent to a peer that was never acked
+ */
+pub struct LostPacket {
+    pub data: Vec<u8>,
+    pub lp: LpPacket,
+    pub addr: SocketAddr,
+}
+
+/**
+ * NDNLPv2 link reliability, shared by the send and receive side of faces
+ * Frames carry a TxSequence, peers return Acks, and frames that are not
+ * acknowledged within the RTO are retransmitted a bounded number of times
+ */
+pub struct Reliability {
+    peers: HashMap<SocketAddr, Peer>,
+    pub retransmissions: u64,
+    pub losses: u64,
+}
+
+impl Reliability {
+    pub fn new() -> Reliability {
+        Reliability {
+            peers: HashMap::new(),
+            retransmissions: 0,
+            losses: 0,
+        }
+    }
+
+    fn peer(&mut self, addr: SocketAddr) -> &mut Peer {
+        self.peers.entry(addr).or_insert_with(|| Peer {
+            next_tx_sequence: initial_sequence(),
+            unacked: HashMap::new(),
+            acks: Vec::new(),
+            acks_since: 0,
+            rtt: FaceInfo::new(),
+            last_used: now_ms(),
+        })
+    }
+
+    /**
+     * Drop the state of a peer, pending frames are neither retransmitted nor reported
+     */
+    pub fn forget(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+
+    /**
+     * Prepare an outgoing frame, wrapping it in an LpPacket if needed
+     */
+    pub fn send(&mut self, frame: Vec<u8>, addr: SocketAddr) -> Vec<u8> {
+        let lp = match LpPacket::decode(&frame) {
+            Ok((lp, _)) => lp,
+            Err(_) => LpPacket::new(frame),
+        };
+        self.peer(addr).transmit(lp, now_ms(), 0)
+    }
+
+    /**
+     * Process the reliability fields of a received LpPacket
+     */
+    pub fn receive(&mut self, lp: &mut LpPacket, addr: SocketAddr) {
+        let now = now_ms();
+        let peer = self.peer(addr);
+        peer.last_used = now;
+
+        if let Some(tx_sequence) = lp.tx_sequence.take() {
+            if peer.acks.is_empty() {
+                peer.acks_since = now;
+            }
+            peer.acks.push(tx_sequence);
+        }
+
+        for ack in lp.acks.drain(..) {
+            if let Some(frame) = peer.unacked.remove(&ack) {
+                // Only frames sent once give a valid RTT sample
+                if frame.retx == 0 {
+                    peer.rtt.add_rtt(now.saturating_sub(frame.sent_at));
+                }
+            }
+        }
+    }
+
+    /**
+     * Retransmit timed out frames and send acks that waited too long
+     * Returns the frames to send, and the network layer packets that
+     * were given up on with their link headers
+     */
+    pub fn poll(&mut self) -> (Vec<(Vec<u8>, SocketAddr)>, Vec<LostPacket>) {
+        self.poll_at(now_ms())
+    }
+
+    fn poll_at(&mut self, now: u64) -> (Vec<(Vec<u8>, SocketAddr)>, Vec<LostPacket>) {
+        let mut frames = Vec::new();
+        let mut lost = Vec::new();
+
+        for (addr, peer) in self.peers.iter_mut() {
+            let rto = peer.rto();
+            let expired: Vec<u64> = peer.unacked.iter()
+                .filter(|(_, f)| now.saturating_sub(f.sent_at) >= rto)
+                .map(|(s, _)| *s)
+                .collect();
+
+            for tx_sequence in expired {
+                let frame = peer.unacked.remove(&tx_sequence).unwrap();
+                if frame.retx >= RELIABILITY_MAX_RETX {
+                    self.losses += 1;
+                    peer.rtt.add_timeout();
+
+                    // Only whole network layer packets can be reported
+                    let mut lp = frame.lp;
+                    if lp.frag_count.is_none_or(|c| c <= 1) {
+                        if let Some(data) = lp.fragment.take() {
+                            lost.push(LostPacket { data, lp, addr: *addr });
+                        }
+                    }
+                    continue;
+                }
+
+                self.retransmissions += 1;
+                frames.push((peer.transmit(frame.lp, now, frame.retx + 1), *addr));
+            }
+
+            // Send acks without waiting for traffic to piggyback on
+            if !peer.acks.is_empty() && now.saturating_sub(peer.acks_since) >= RELIABILITY_ACK_DELAY {
+                let idle = LpPacket { acks: peer.take_acks(), ..Default::default() };
+                frames.push((idle.encode(), *addr));
+                peer.acks_since = now;
+            }
+        }
+
+        self.peers.retain(|_, p| {
+            !p.unacked.is_empty() || !p.acks.is_empty() || now.saturating_sub(p.last_used) < RELIABILITY_PEER_TIMEOUT
+        });
+
+        (frames, lost)
+    }
+}
+
+/**
+ * Encode a network layer packet for sending
+ * The packet is only wrapped in an LpPacket if it needs link headers
+ */
+pub fn wrap(packet: Vec<u8>, mut headers: LpPacket) -> Vec<u8> {
+    if headers.is_bare() {
+        return packet;
+    }
+    headers.fragment = Some(packet);
+    headers.encode()
+}
+
+/**
+ * Encode a packet sent in reply to a downstream, echoing its PIT token
+ */
+pub fn wrap_reply(packet: Vec<u8>, pit_token: Option<&Vec<u8>>) -> Vec<u8> {
+    let headers = LpPacket { pit_token: pit_token.cloned(), ..Default::default() };
+    wrap(packet, headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    fn reassemble(reassembler: &mut Reassembler, frames: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        frames.iter()
+            .filter_map(|f| reassembler.receive(f, 256, None))
+            .map(|p| p.data)
+            .collect()
+    }
+
+    #[test]
+    fn fragment_round_trip() {
+        let mut fragmenter = Fragmenter::new(200);
+        let frames = fragmenter.fragment(packet(1000));
+        assert!(frames.len() > 1);
+        assert!(frames.iter().all(|f| f.len() <= 200));
+
+        let mut reassembler = Reassembler::new(None);
+        assert_eq!(reassemble(&mut reassembler, &frames), vec![packet(1000)]);
+        assert!(reassembler.partial.is_empty());
+
+        // Small packets are not fragmented
+        assert_eq!(fragmenter.fragment(packet(100)), vec![packet(100)]);
+    }
+
+    #[test]
+    fn fragments_out_of_order() {
+        let mut fragmenter = Fragmenter::new(200);
+        let mut frames = fragmenter.fragment(packet(1000));
+        frames.reverse();
+
+        let mut reassembler = Reassembler::new(None);
+        assert_eq!(reassemble(&mut reassembler, &frames), vec![packet(1000)]);
+    }
+
+    #[test]
+    fn duplicate_fragment() {
+        let mut fragmenter = Fragmenter::new(200);
+        let frames = fragmenter.fragment(packet(1000));
+        let mut with_dup = vec![frames[0].clone()];
+        with_dup.extend(frames.iter().cloned());
+
+        let mut reassembler = Reassembler::new(None);
+        assert_eq!(reassemble(&mut reassembler, &with_dup), vec![packet(1000)]);
+        assert!(reassembler.partial.is_empty());
+    }
+
+    #[test]
+    fn oversize_frag_count() {
+        let lp = LpPacket {
+            sequence: Some(1),
+            frag_index: Some(0),
+            frag_count: Some(8800),
+            fragment: Some(packet(100)),
+            ..Default::default()
+        };
+
+        let mut reassembler = Reassembler::new(None);
+        assert!(reassembler.receive(&lp.encode(), 256, None).is_none());
+        assert!(reassembler.partial.is_empty());
+    }
+
+    #[test]
+    fn partial_packets_per_face() {
+        let mut reassembler = Reassembler::new(None);
+        for i in 0..(REASSEMBLY_MAX_PARTIAL_PER_FACE as u64 + 1) {
+            let lp = LpPacket {
+                sequence: Some(i * 2),
+                frag_index: Some(0),
+                frag_count: Some(2),
+                fragment: Some(packet(100)),
+                ..Default::default()
+            };
+            reassembler.receive(&lp.encode(), 256, None);
+        }
+        assert_eq!(reassembler.partial.len(), REASSEMBLY_MAX_PARTIAL_PER_FACE);
+    }
+
+    fn addrs() -> (SocketAddr, SocketAddr) {
+        ("127.0.0.1:6001".parse().unwrap(), "127.0.0.1:6002".parse().unwrap())
+    }
+
+    #[test]
+    fn reliability_ack() {
+        let (a, b) = addrs();
+        let sender = Arc::new(Mutex::new(Reliability::new()));
+        let receiver = Arc::new(Mutex::new(Reliability::new()));
+        let mut sender_in = Reassembler::new(Some(sender.clone()));
+        let mut receiver_in = Reassembler::new(Some(receiver.clone()));
+
+        // The frame carries a TxSequence and the packet is delivered
+        let frame = sender.lock().unwrap().send(packet(100), b);
+        assert!(LpPacket::decode(&frame).unwrap().0.tx_sequence.is_some());
+        let delivered = receiver_in.receive(&frame, 256, Some(a)).unwrap();
+        assert_eq!(delivered.data, packet(100));
+
+        // The receiver acks after the delay, which clears the sender's state
+        let (acks, lost) = receiver.lock().unwrap().poll_at(now_ms() + RELIABILITY_ACK_DELAY);
+        assert!(lost.is_empty());
+        assert_eq!(acks.len(), 1);
+        assert_eq!(acks[0].1, a);
+        assert!(sender_in.receive(&acks[0].0, 256, Some(b)).is_none());
+        assert!(sender.lock().unwrap().peers[&b].unacked.is_empty());
+    }
+
+    #[test]
+    fn reliability_retransmit_and_loss() {
+        let (_, b) = addrs();
+        let mut reliability = Reliability::new();
+        let first = reliability.send(packet(100), b);
+        let first_sequence = LpPacket::decode(&first).unwrap().0.tx_sequence;
+
+        // Unacked frames are retransmitted with a new TxSequence
+        let mut now = now_ms();
+        for retx in 1..=RELIABILITY_MAX_RETX {
+            now += RELIABILITY_MAX_RTO;
+            let (frames, lost) = reliability.poll_at(now);
+            assert!(lost.is_empty());
+            assert_eq!(frames.len(), 1);
+            let (lp, _) = LpPacket::decode(&frames[0].0).unwrap();
+            assert_ne!(lp.tx_sequence, first_sequence);
+            assert_eq!(lp.fragment, Some(packet(100)));
+            assert_eq!(reliability.retransmissions, retx as u64);
+        }
+
+        // Then the packet is reported lost to the pipelines
+        now += RELIABILITY_MAX_RTO;
+        let (frames, lost) = reliability.poll_at(now);
+        assert!(frames.is_empty());
+        assert_eq!(lost.len(), 1);
+        assert_eq!(lost[0].data, packet(100));
+        assert_eq!(lost[0].addr, b);
+        assert_eq!(reliability.losses, 1);
+    }
+
+    #[test]
+    fn reliability_disabled_face() {
+        let receiver = Arc::new(Mutex::new(Reliability::new()));
+        let mut receiver_in = Reassembler::new(Some(receiver.clone()));
+
+        // Faces without reliability deliver the packet but never ack
+        let lp = LpPacket { tx_sequence: Some(1), fragment: Some(packet(100)), ..Default::default() };
+        assert!(receiver_in.receive(&lp.encode(), 256, None).is_some());
+        let (frames, _) = receiver.lock().unwrap().poll_at(now_ms() + RELIABILITY_ACK_DELAY);
+        assert!(frames.is_empty());
+    }
+}
@@ -1,17 +1,50 @@
-use std::{sync::Arc, net::SocketAddr};
+use std::net::SocketAddr;
+use std::sync::Arc;
 
 use crossbeam::deque::Injector;
 
+use table::TableConfig;
+use table::pit::PITFullPolicy;
+
+mod face;
 mod socket;
-mod tlv;
+use rnfd::tlv;
 mod dispatch;
+mod link;
 mod pipeline;
 mod table;
 mod mgmt;
+mod stream;
+mod tcp;
+mod usocket;
 
 const NUM_DISPATCH_THREADS: usize = 8;
 const NUM_PIPELINE_THREADS: usize = 8;
 
+// Table limits per pipeline thread, the environment variables override them
+const CS_CAPACITY: usize = 16384;
+const CS_CAPACITY_ENV: &str = "RNFD_CS_CAPACITY";
+const PIT_MAX_SIZE: usize = 1 << 20;
+const PIT_MAX_SIZE_ENV: &str = "RNFD_PIT_MAX_SIZE";
+const PIT_FULL_POLICY: PITFullPolicy = PITFullPolicy::Drop;
+const PIT_FULL_POLICY_ENV: &str = "RNFD_PIT_FULL_POLICY";
+
+// UDP listening addresses, IPv6 sockets only serve IPv6 peers
+// RNFD_UDP_LISTEN gives a comma-separated list instead, and setting
+// RNFD_UDP_LOOPBACK=1 restricts the defaults to loopback as a hardening choice
+const UDP_LISTEN_ADDRS: &str = "0.0.0.0:7766,[::]:7766";
+const UDP_LOOPBACK_ADDRS: &str = "127.0.0.1:7766,[::1]:7766";
+const UDP_LISTEN_ENV: &str = "RNFD_UDP_LISTEN";
+const UDP_LOOPBACK_ENV: &str = "RNFD_UDP_LOOPBACK";
+const NUM_UDP_LISTENERS: usize = 4;
+
+// TCP listening addresses, outgoing TCP faces are made through them too
+const TCP_LISTEN_ADDRS: [&str; 2] = ["0.0.0.0:6363", "[::]:6363"];
+
+// Unix socket for local applications, the environment variable overrides the path
+const UNIX_SOCKET_PATH: &str = "/run/nfd.sock";
+const UNIX_SOCKET_ENV: &str = "RNFD_UNIX_SOCKET";
+
 fn main() {
     // Connection-to-dispatcher queue
     let q1 = Arc::new(Injector::<Arc<socket::UdpPacket>>::new());
@@ -26,6 +59,9 @@ fn main() {
     // Dispatcher-to-management queue
     let qm = Arc::new(Injector::<Arc<socket::UdpPacket>>::new());
 
+    // Faces shared by pipelines, management and connections
+    let faces = Arc::new(face::FaceTable::new());
+
     // Start dispatch threads
     let mut dispatchers = Vec::new();
     for i in 0..NUM_DISPATCH_THREADS {
@@ -36,35 +72,86 @@ fn main() {
         for qs in &pipeline_queues {
             queues.push(qs.clone());
         }
-        dispatchers.push(dispatch::thread(q1.clone(), qm.clone(), queues));
+        dispatchers.push(dispatch::thread(q1.clone(), qm.clone(), queues, faces.clone()));
     }
 
-    // Pipeline to connection queue
-    let q3 = Arc::new(Injector::<(Vec<u8>, SocketAddr)>::new());
+    // UDP channels, their faces queue packets to the listeners
+    let mut udp_channels = Vec::new();
+    let udp_default = match std::env::var(UDP_LOOPBACK_ENV).as_deref() {
+        Ok("1") => UDP_LOOPBACK_ADDRS,
+        _ => UDP_LISTEN_ADDRS,
+    };
+    let udp_addrs = std::env::var(UDP_LISTEN_ENV).unwrap_or_else(|_| udp_default.to_string());
+    for path in udp_addrs.split(',').map(str::trim) {
+        if path.parse::<SocketAddr>().is_err() {
+            println!("Invalid UDP listening address {}", path);
+            continue;
+        }
+        let udp = Arc::new(socket::UdpChannel::new(path));
+        faces.add_channel(udp.clone());
+        udp_channels.push(udp);
+    }
+
+    // TCP channels, each face has its own connection and send queue
+    let mut tcp_channels = Vec::new();
+    for path in TCP_LISTEN_ADDRS {
+        let tcp = Arc::new(tcp::TcpChannel::new(path, Arc::downgrade(&faces), q1.clone()));
+        faces.add_channel(tcp.clone());
+        tcp_channels.push(tcp);
+    }
 
     { // Start management thread
         let mut queues = Vec::new();
         for qs in &pipeline_queues {
             queues.push(qs.clone());
         }
-        mgmt::thread(qm.clone(), q3.clone(), queues);
+        mgmt::thread(qm.clone(), faces.clone(), queues);
     }
 
     // Start pipeline threads
+    let config = TableConfig {
+        cs_capacity: read_env(CS_CAPACITY_ENV, CS_CAPACITY, |v| v.parse().ok()),
+        pit_max_size: read_env(PIT_MAX_SIZE_ENV, PIT_MAX_SIZE, |v| v.parse().ok()),
+        pit_full_policy: read_env(PIT_FULL_POLICY_ENV, PIT_FULL_POLICY, PITFullPolicy::from_name),
+    };
     let mut pipelines = Vec::new();
     for i in 0..NUM_PIPELINE_THREADS {
         println!("Starting pipeline thread {i}");
-        pipelines.push(pipeline::incoming::thread(pipeline_queues[i as usize].clone(), q3.clone()));
+        pipelines.push(pipeline::incoming::thread(pipeline_queues[i as usize].clone(), faces.clone(), config));
     }
 
     // Start listening for data
-    socket::listen_udp("127.0.0.1:7766", q1.clone(), q3.clone()).unwrap();
-    socket::listen_udp("127.0.0.1:7766", q1.clone(), q3.clone()).unwrap();
-    socket::listen_udp("127.0.0.1:7766", q1.clone(), q3.clone()).unwrap();
-    socket::listen_udp("127.0.0.1:7766", q1.clone(), q3.clone()).unwrap();
+    for udp in &udp_channels {
+        for _ in 0..NUM_UDP_LISTENERS {
+            socket::listen_udp(udp.clone(), faces.clone(), q1.clone()).unwrap();
+        }
+    }
+
+    for tcp in tcp_channels {
+        tcp::listen_tcp(tcp).unwrap();
+    }
+
+    // Local applications get a local-scope face per connection
+    let unix_path = std::env::var(UNIX_SOCKET_ENV).unwrap_or_else(|_| UNIX_SOCKET_PATH.to_string());
+    if let Err(e) = usocket::listen_unix(&unix_path, faces.clone(), q1.clone()) {
+        println!("Cannot listen on {}: {:?}", unix_path, e);
+    }
 
     // Join all pipelines threads
     for t in pipelines {
         t.join().unwrap();
     }
 }
+
+/**
+ * Read a setting from the environment, keeping the default if unset or invalid
+ */
+fn read_env<T>(var: &str, default: T, parse: impl FnOnce(&str) -> Option<T>) -> T {
+    match std::env::var(var) {
+        Ok(v) => parse(&v).unwrap_or_else(|| {
+            println!("Invalid value {} for {}", v, var);
+            default
+        }),
+        Err(_) => default,
+    }
+}
@@ -0,0 +1,32 @@
+use std::io;
+use crate::{table::Table, tlv};
+
+pub fn list(table: &Table) -> Result<(), io::Error> {
+    let mut faces = table.faces.list();
+    faces.sort_by_key(|f| f.id());
+    for face in faces {
+        println!("YaNFD: Face {} remote={} local={} {:?} {:?} {:?} mtu={}",
+            face.id(), face.remote_uri(), face.local_uri(), face.scope(),
+            face.persistency(), face.link_type(), face.mtu());
+    }
+    Ok(())
+}
+
+pub fn read_set_reliability(table: &mut Table, mut frame: &[u8]) -> Result<(), io::Error> {
+    // Face
+    let addr_tlo = tlv::vec_decode::read_tlo(frame)?;
+    let face = super::read_face(frame, &table.faces)?;
+    frame = &frame[addr_tlo.o+addr_tlo.l as usize..];
+
+    // Enabled flag
+    let flag_tlo = tlv::vec_decode::read_tlo(frame)?;
+    let enabled = tlv::vec_decode::read_nni(&frame[flag_tlo.o..], flag_tlo.l)? != 0;
+
+    println!("YaNFD: Setting link reliability {} on face {}", enabled, face);
+
+    match table.faces.get(face) {
+        Some(f) if f.set_reliability(enabled) => Ok(()),
+        Some(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "Face does not support link reliability")),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "No such face")),
+    }
+}
@@ -1,25 +1,71 @@
 use std::io;
-use crate::{table::{Table, pit::NextHop}, tlv};
+use crate::{table::{Table, fib::NextHop}, tlv};
 
 pub fn read_insert_hop(table: &mut Table, mut frame: &[u8]) -> Result<(), io::Error> {
     // Name
     let name_tlo = tlv::vec_decode::read_tlo(&frame[..])?;
+    if name_tlo.o + name_tlo.l as usize > frame.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Incorrect Name TLV encoding"));
+    }
     let name = &frame[name_tlo.o..name_tlo.o+name_tlo.l as usize].to_vec();
     frame = &frame[name_tlo.o+name_tlo.l as usize..];
 
-    // Address
+    // Face
     let addr_tlo = tlv::vec_decode::read_tlo(&frame[..])?;
-    let addr = super::read_addr(frame)?;
+    let face = super::read_face(frame, &table.faces)?;
     frame = &frame[addr_tlo.o+addr_tlo.l as usize..];
 
     // Cost
     let cost_tlo = tlv::vec_decode::read_tlo(&frame[..])?;
     let cost = tlv::vec_decode::read_nni(&frame[cost_tlo.o..], cost_tlo.l)?;
 
-    println!("YaNFD: Inserting hop {:?} {} {}", name, addr.to_string(), cost);
+    println!("YaNFD: Inserting hop {} {} {}", tlv::name::uri(name), face, cost);
 
-    let (node, _, _) = table.pit.insert_or_get(name)?;
-    node.borrow_mut().insert_hop(NextHop { addr, cost });
+    table.fib.insert_hop(name, NextHop { face, cost })
+}
 
+pub fn read_remove_hop(table: &mut Table, mut frame: &[u8]) -> Result<(), io::Error> {
+    // Name
+    let name_tlo = tlv::vec_decode::read_tlo(frame)?;
+    if name_tlo.o + name_tlo.l as usize > frame.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Incorrect Name TLV encoding"));
+    }
+    let name = &frame[name_tlo.o..name_tlo.o+name_tlo.l as usize].to_vec();
+    frame = &frame[name_tlo.o+name_tlo.l as usize..];
+
+    // Face
+    let face = super::read_face(frame, &table.faces)?;
+
+    println!("YaNFD: Removing hop {} {}", tlv::name::uri(name), face);
+
+    if !table.fib.remove_hop(name, face)? {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "No such nexthop"));
+    }
+
+    Ok(())
+}
+
+pub fn list(table: &Table) -> Result<(), io::Error> {
+    for (name, nexthops) in table.fib.list() {
+        println!("YaNFD: FIB {} {:?}", tlv::name::uri(&name), nexthops);
+    }
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::face::FaceTable;
+    use crate::table::{TableConfig, pit::PITFullPolicy};
+
+    #[test]
+    fn truncated_name() {
+        let config = TableConfig { cs_capacity: 16, pit_max_size: 16, pit_full_policy: PITFullPolicy::Drop };
+        let mut table = Table::new(Arc::new(FaceTable::new()), config);
+
+        // Name TLV claims 10 bytes but only 3 follow
+        let frame = [7, 10, 8, 1, 97];
+        assert!(read_insert_hop(&mut table, &frame).is_err());
+        assert!(read_remove_hop(&mut table, &frame).is_err());
+    }
+}
@@ -1,10 +1,15 @@
+mod face;
 mod fib;
+mod policy;
+mod region;
+mod strategy;
 
 use std::io::Read;
 use std::io::Write;
 use std::os::unix::net::UnixStream;
 use std::{sync::Arc, net::SocketAddr};
 use crossbeam::deque::Injector;
+use crate::face::{FaceId, FacePersistency, FaceTable, INTERNAL_FACE_ID};
 use crate::socket::UdpPacket;
 use crate::table::Table;
 use crate::tlv;
@@ -12,7 +17,7 @@ use crate::tlv::varnumber::VarNumber;
 
 pub fn thread(
     chan_in: Arc<Injector<Arc<UdpPacket>>>,
-    chan_out: Arc<Injector::<(Vec<u8>, SocketAddr)>>,
+    faces: Arc<FaceTable>,
     chans_pipeline: Vec<Arc<Injector<Arc<UdpPacket>>>>,
 ) {
     // Connect to YaNFD socket
@@ -27,13 +32,13 @@ pub fn thread(
 
     // Read from YaNFD socket
     std::thread::spawn(move || {
-        read_yanfd(stream_arc_read, chan_out, chans_pipeline)
+        read_yanfd(stream_arc_read, faces, chans_pipeline)
     });
 }
 
 fn read_yanfd(
     stream_arc: Arc<UnixStream>,
-    chan_out: Arc<Injector<(Vec<u8>, SocketAddr)>>,
+    faces: Arc<FaceTable>,
     chans_pipeline: Vec<Arc<Injector<Arc<UdpPacket>>>>,
 ) {
     loop {
@@ -45,13 +50,13 @@ fn read_yanfd(
         }
 
         let frame = &buf[..len];
-        read_yanfd_frame(frame, &chan_out, &chans_pipeline);
+        read_yanfd_frame(frame, &faces, &chans_pipeline);
     }
 }
 
 fn read_yanfd_frame(
     frame: &[u8],
-    chan_out: &Arc<Injector<(Vec<u8>, SocketAddr)>>,
+    faces: &FaceTable,
     chans_pipeline: &Vec<Arc<Injector<Arc<UdpPacket>>>>,
 ) {
     let frame_tlo = tlv::vec_decode::read_tlo(frame);
@@ -62,11 +67,11 @@ fn read_yanfd_frame(
 
     let c_frame = &frame[frame_tlo.o..];
     if frame_tlo.t == 6 {
-        let res = read_yanfd_data_frame(c_frame);
+        let res = read_yanfd_data_frame(c_frame, faces);
         match res {
-            Ok((addr, data)) => {
-                println!("YaNFD: read {} bytes for {:?}", data.len(), addr);
-                chan_out.push((data, addr));
+            Ok((face, data)) => {
+                println!("YaNFD: read {} bytes for face {}", data.len(), face);
+                faces.send(face, data);
             },
             Err(e) => {
                 println!("YaNFD: parsing error {:?}", e);
@@ -80,17 +85,19 @@ fn read_yanfd_frame(
 fn read_yanfd_mgmt_frame(frame: &[u8], chans_pipeline: &Vec<Arc<Injector<Arc<UdpPacket>>>>) {
     let pack = Arc::new(UdpPacket {
         data: frame.to_vec(),
-        addr: SocketAddr::from(([0, 0, 0, 0], 0)),
+        face: INTERNAL_FACE_ID,
+        lp: None,
+        lost: false,
     });
     for chan in chans_pipeline {
         chan.push(pack.clone());
     }
 }
 
-fn read_yanfd_data_frame(frame: &[u8]) -> Result<(SocketAddr, Vec<u8>), std::io::Error> {
-    // Get address (TLV type 4)
+fn read_yanfd_data_frame(frame: &[u8], faces: &FaceTable) -> Result<(FaceId, Vec<u8>), std::io::Error> {
+    // Get face (TLV type 4)
     let addr_tlo = tlv::vec_decode::read_tlo(frame)?;
-    let addr = read_addr(&frame)?;
+    let face = read_face(frame, faces)?;
 
     // Peel off link layer header
     let mut data = &frame[addr_tlo.o+addr_tlo.l as usize..];
@@ -100,7 +107,7 @@ fn read_yanfd_data_frame(frame: &[u8]) -> Result<(SocketAddr, Vec<u8>), std::io:
         data_tlo = tlv::vec_decode::read_tlo(data)?;
     }
 
-    Ok((addr, data.to_vec()))
+    Ok((face, data.to_vec()))
 }
 
 fn send_yanfd(chan_in: Arc<Injector<Arc<UdpPacket>>>, stream_arc: Arc<UnixStream>) {
@@ -122,7 +129,7 @@ fn send_yanfd(chan_in: Arc<Injector<Arc<UdpPacket>>>, stream_arc: Arc<UnixStream
 
         let mut stream = &*stream_arc;
 
-        let addr_str = packet.addr.to_string();
+        let addr_str = packet.face.to_string();
         let addr_bytes = addr_str.as_bytes();
         let addr_len = addr_bytes.len() as usize;
 
@@ -146,22 +153,40 @@ fn send_yanfd(chan_in: Arc<Injector<Arc<UdpPacket>>>, stream_arc: Arc<UnixStream
     }
 }
 
-pub fn read_addr(frame: &[u8]) -> Result<SocketAddr, std::io::Error> {
+/**
+ * Read a face (TLV type 4) given as a FaceId, a face URI or a UDP address
+ * Faces to URIs and addresses are created if they do not exist yet
+ */
+pub fn read_face(frame: &[u8], faces: &FaceTable) -> Result<FaceId, std::io::Error> {
     let addr_tlo = tlv::vec_decode::read_tlo(frame)?;
     if addr_tlo.t != 4 {
         return Err(std::io::Error::new(std::io::ErrorKind::Other, "Expected TLV type 4"));
     }
+    if addr_tlo.o + addr_tlo.l as usize > frame.len() {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Unexpected EOF"));
+    }
     let addr = &frame[addr_tlo.o..addr_tlo.o+addr_tlo.l as usize];
     let addr_str = std::str::from_utf8(addr);
     if addr_str.is_err() {
         return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid address"));
     }
-    let addr = addr_str.unwrap().parse::<SocketAddr>();
-    if addr.is_err() {
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid address"));
+    let addr_str = addr_str.unwrap();
+
+    if let Ok(id) = addr_str.parse::<FaceId>() {
+        return match faces.get(id) {
+            Some(_) => Ok(id),
+            None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No such face")),
+        };
+    }
+
+    let uri = match addr_str.parse::<SocketAddr>() {
+        Ok(addr) => crate::socket::udp_uri(&addr),
+        Err(_) => addr_str.to_string(),
+    };
+    match faces.connect(&uri, FacePersistency::Persistent) {
+        Some(id) => Ok(id),
+        None => Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid address")),
     }
-    let addr = addr.unwrap();
-    Ok(addr)
 }
 
 pub fn process_frame(table: &mut Table, packet: Arc<UdpPacket>) {
@@ -175,6 +200,20 @@ pub fn process_frame(table: &mut Table, packet: Arc<UdpPacket>) {
     let res;
     if tlo.t == 1 {
         res = fib::read_insert_hop(table, frame);
+    } else if tlo.t == 2 {
+        res = strategy::read_set_strategy(table, frame);
+    } else if tlo.t == 3 {
+        res = region::read_add_region(table, frame);
+    } else if tlo.t == 4 {
+        res = policy::read_set_unsolicited_policy(table, &frame[..(tlo.l as usize).min(frame.len())]);
+    } else if tlo.t == 5 {
+        res = fib::read_remove_hop(table, frame);
+    } else if tlo.t == 6 {
+        res = fib::list(table);
+    } else if tlo.t == 7 {
+        res = face::list(table);
+    } else if tlo.t == 8 {
+        res = face::read_set_reliability(table, frame);
     } else {
         res = Ok(());
         println!("Unknown MGMT frame type {}", tlo.t);
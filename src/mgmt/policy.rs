@@ -0,0 +1,18 @@
+use std::io;
+use crate::{pipeline::data::UnsolicitedDataPolicy, table::Table};
+
+pub fn read_set_unsolicited_policy(table: &mut Table, frame: &[u8]) -> Result<(), io::Error> {
+    // Policy name as a string
+    let name = std::str::from_utf8(frame)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid policy name"))?;
+
+    let policy = match UnsolicitedDataPolicy::from_name(name) {
+        Some(policy) => policy,
+        None => return Err(io::Error::new(io::ErrorKind::NotFound, "Unknown unsolicited data policy")),
+    };
+
+    println!("YaNFD: Setting unsolicited data policy {:?}", policy);
+    table.unsolicited_policy = policy;
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use std::io;
+use crate::{table::Table, tlv};
+
+pub fn read_add_region(table: &mut Table, frame: &[u8]) -> Result<(), io::Error> {
+    // Name
+    let name_tlo = tlv::vec_decode::read_tlo(frame)?;
+    if name_tlo.o + name_tlo.l as usize > frame.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Incorrect name TLV encoding"));
+    }
+    let name = frame[name_tlo.o..name_tlo.o+name_tlo.l as usize].to_vec();
+
+    println!("YaNFD: Adding region {}", tlv::name::uri(&name));
+
+    if !table.regions.contains(&name) {
+        table.regions.push(name);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::io;
+use crate::{table::Table, tlv};
+
+// Strategy name TLV, as in NFD ControlParameters
+const TLV_STRATEGY: u64 = 107;
+
+pub fn read_set_strategy(table: &mut Table, mut frame: &[u8]) -> Result<(), io::Error> {
+    // Name
+    let name_tlo = tlv::vec_decode::read_tlo(frame)?;
+    if name_tlo.o + name_tlo.l as usize > frame.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Incorrect Name TLV encoding"));
+    }
+    let name = &frame[name_tlo.o..name_tlo.o+name_tlo.l as usize].to_vec();
+    frame = &frame[name_tlo.o+name_tlo.l as usize..];
+
+    // Strategy, as a URI string
+    let strategy_tlo = tlv::vec_decode::read_tlo(frame)?;
+    if strategy_tlo.t != TLV_STRATEGY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected Strategy TLV"));
+    }
+    if strategy_tlo.o + strategy_tlo.l as usize > frame.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Incorrect Strategy TLV encoding"));
+    }
+    let strategy_uri = std::str::from_utf8(&frame[strategy_tlo.o..strategy_tlo.o+strategy_tlo.l as usize])
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid strategy name"))?;
+
+    let strategy = match table.strategies.lookup(strategy_uri) {
+        Some(id) => id,
+        None => return Err(io::Error::new(io::ErrorKind::NotFound, "Unknown strategy")),
+    };
+
+    println!("YaNFD: Setting strategy {} {}", tlv::name::uri(name), strategy_uri);
+
+    let (node, _) = table.pit.insert_or_get(name)?;
+    node.borrow_mut().strategy = strategy;
+
+    Ok(())
+}
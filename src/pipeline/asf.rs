@@ -0,0 +1,185 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+use crate::{face::FaceId, table::{Table, now_ms, fib::NextHop, pit::{InRecord, PITNode}, measurements::Measurements}, socket::UdpPacket, tlv};
+use super::{strategy::Strategy, nack::NackReason, Interest};
+
+// Interval between probes to non-best nexthops (ms)
+const PROBE_INTERVAL: u64 = 10000;
+
+// Consecutive timeouts after which a face is ranked last
+const MAX_TIMEOUTS: u64 = 3;
+
+/**
+ * Adaptive SRTT-based forwarding strategy
+ * Measurements are kept on the FIB entry used for the Interest
+ */
+pub struct AsfStrategy {}
+
+impl AsfStrategy {
+    fn with_measurements<R>(node: &Rc<RefCell<PITNode>>, f: impl FnOnce(&mut Measurements) -> R) -> Option<R> {
+        let route = node.borrow().route.upgrade()?;
+        let mut route = route.borrow_mut();
+        let m = route.measurements.get_or_insert_with(|| Box::new(Measurements::new()));
+        Some(f(m))
+    }
+
+    /**
+     * Rank a nexthop, lower is better
+     * Measured faces come first by SRTT, then unmeasured faces by cost,
+     * then faces that keep timing out
+     */
+    fn rank(m: &Measurements, hop: &NextHop) -> (u8, u64) {
+        match m.faces.get(&hop.face) {
+            Some(info) if info.timeouts >= MAX_TIMEOUTS => (2, hop.cost),
+            Some(info) => match info.srtt {
+                Some(srtt) => (0, (srtt * 1000.0) as u64),
+                None => (1, hop.cost),
+            },
+            None => (1, hop.cost),
+        }
+    }
+
+    /**
+     * Pick a nexthop to probe among all but the best one
+     * Faces never tried come first by cost, then the face probed least recently,
+     * so faces that keep timing out are retried in turn
+     * Probing halves the timeouts of the face, letting it recover
+     */
+    fn probe(m: &mut Measurements, candidates: &[NextHop], now: u64) -> Option<NextHop> {
+        let hop = candidates.get(1..)?.iter()
+            .min_by_key(|n| {
+                let info = m.faces.get(&n.face);
+                let tried = info.is_some_and(|i| i.srtt.is_some() || i.timeouts > 0 || i.last_probe.is_some());
+                (tried, info.and_then(|i| i.last_probe), Self::rank(m, n))
+            })
+            .copied()?;
+
+        let info = m.face(hop.face);
+        info.last_probe = Some(now);
+        info.timeouts /= 2;
+        Some(hop)
+    }
+
+    fn record_timeouts(node: &Rc<RefCell<PITNode>>, faces: Vec<FaceId>) {
+        Self::with_measurements(node, |m| {
+            for face in faces {
+                m.face(face).add_timeout();
+            }
+        });
+    }
+}
+
+impl Strategy for AsfStrategy {
+    fn after_receive_interest(&self, table: &mut Table, packet: Arc<UdpPacket>, interest: Interest) {
+        let mut candidates: Vec<NextHop> = interest.nexthops.as_ref().unwrap()
+            .iter()
+            .filter(|n| n.face != packet.face)
+            .copied()
+            .collect();
+
+        if candidates.is_empty() {
+            println!("No nexthops for interest: {}", tlv::name::uri(&interest.name));
+            super::nack::reject_interest(table, &packet, &interest, NackReason::NoRoute);
+            return;
+        }
+
+        // Rank nexthops and decide whether to probe another one
+        // Without measurements this falls back to the cheapest nexthop
+        candidates.sort_by_key(|n| n.cost);
+        let now = now_ms();
+        let node = interest.pit_node.clone().unwrap();
+        let probe = Self::with_measurements(&node, |m| {
+            candidates.sort_by_key(|n| Self::rank(m, n));
+            if candidates.len() < 2 || m.last_probe.is_some_and(|t| now - t < PROBE_INTERVAL) {
+                return None;
+            }
+            m.last_probe = Some(now);
+            Self::probe(m, &candidates, now)
+        }).flatten();
+
+        let mut res_hops = vec![candidates[0]];
+        res_hops.extend(probe);
+
+        // Call back to forwarder pipeline
+        super::interest::on_outgoing_interest(table, packet, interest, res_hops);
+    }
+
+    fn after_receive_data(&self, table: &mut Table, packet: Arc<UdpPacket>, node: Rc<RefCell<PITNode>>, in_records: Vec<InRecord>) {
+        // Measure RTT of the upstream that answered
+        let upstream = packet.face;
+        let sent = node.borrow().out_records.values()
+            .find(|r| r.face == upstream)
+            .map(|r| r.timestamp);
+        if let Some(sent) = sent {
+            let rtt = now_ms().saturating_sub(sent);
+            Self::with_measurements(&node, |m| m.face(upstream).add_rtt(rtt));
+        }
+
+        for in_record in in_records {
+            let data = crate::link::wrap_reply(packet.data.clone(), in_record.pit_token.as_ref());
+            table.send(in_record.face, data);
+        }
+    }
+
+    fn after_receive_nack(&self, table: &mut Table, face: FaceId, _reason: NackReason, node: Rc<RefCell<PITNode>>) {
+        Self::record_timeouts(&node, vec![face]);
+        super::nack::forward_nack(table, node);
+    }
+
+    fn after_interest_loss(&self, _table: &mut Table, face: FaceId, node: Rc<RefCell<PITNode>>) {
+        Self::record_timeouts(&node, vec![face]);
+    }
+
+    fn before_expire_pending_interest(&self, _table: &mut Table, node: Rc<RefCell<PITNode>>) {
+        // Every upstream that did not answer has timed out
+        let faces = node.borrow().out_records.values()
+            .filter(|r| r.nack.is_none())
+            .map(|r| r.face)
+            .collect();
+        Self::record_timeouts(&node, faces);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hop(face: FaceId, cost: u64) -> NextHop {
+        NextHop { face, cost }
+    }
+
+    #[test]
+    fn probe_prefers_unmeasured() {
+        let mut m = Measurements::new();
+        m.face(256).add_rtt(10);
+        m.face(257).add_rtt(20);
+        for _ in 0..MAX_TIMEOUTS {
+            m.face(258).add_timeout();
+        }
+
+        let mut candidates = vec![hop(256, 1), hop(257, 1), hop(258, 1), hop(259, 5), hop(260, 3)];
+        candidates.sort_by_key(|n| AsfStrategy::rank(&m, n));
+        assert_eq!(candidates[0].face, 256);
+        assert_eq!(AsfStrategy::probe(&mut m, &candidates, 100).map(|n| n.face), Some(260));
+        assert!(AsfStrategy::probe(&mut m, &candidates[..1], 100).is_none());
+    }
+
+    #[test]
+    fn probe_retries_timed_out_faces() {
+        let mut m = Measurements::new();
+        m.face(256).add_rtt(10);
+        m.face(257).add_rtt(20);
+        for _ in 0..MAX_TIMEOUTS {
+            m.face(258).add_timeout();
+        }
+        let candidates = vec![hop(256, 1), hop(257, 1), hop(258, 1)];
+
+        // Probes go round the non-best faces, including the one timing out
+        assert_eq!(AsfStrategy::probe(&mut m, &candidates, 100).map(|n| n.face), Some(257));
+        assert_eq!(AsfStrategy::probe(&mut m, &candidates, 200).map(|n| n.face), Some(258));
+        assert_eq!(AsfStrategy::probe(&mut m, &candidates, 300).map(|n| n.face), Some(257));
+
+        // The probed face is no longer ranked last
+        assert!(m.faces[&258].timeouts < MAX_TIMEOUTS);
+        assert_eq!(m.faces[&258].last_probe, Some(200));
+    }
+}
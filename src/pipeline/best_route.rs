@@ -1,31 +1,46 @@
 use std::sync::Arc;
-use crate::{table::Table, socket::UdpPacket};
-use super::{strategy::Strategy, Interest};
+use crate::{face::FaceId, table::{Table, fib::NextHop}, socket::UdpPacket, tlv};
+use super::{strategy::Strategy, nack::NackReason, Interest};
 
 pub struct BestRouteStrategy {}
 
-impl Strategy for BestRouteStrategy {
-    fn after_receive_interest(table: &mut Table, packet: Arc<UdpPacket>, interest: Interest) {
-        let mut res_hops = Vec::new();
+impl BestRouteStrategy {
+    /**
+     * Cheapest nexthop that does not lead back to the incoming face
+     */
+    fn best(nexthops: &[NextHop], in_face: FaceId) -> Option<NextHop> {
+        nexthops.iter()
+            .filter(|n| n.face != in_face)
+            .min_by_key(|n| n.cost)
+            .copied()
+    }
+}
 
-        let nexthops = interest.nexthops.as_ref().unwrap();
-        if nexthops.len() > 0 {
-            let mut nexthop = &nexthops[0];
-            let mut cost = nexthop.cost;
-            for n in nexthops {
-                if n.cost < cost {
-                    nexthop = n;
-                    cost = n.cost;
-                }
+impl Strategy for BestRouteStrategy {
+    fn after_receive_interest(&self, table: &mut Table, packet: Arc<UdpPacket>, interest: Interest) {
+        let nexthop = match Self::best(interest.nexthops.as_ref().unwrap(), packet.face) {
+            Some(nexthop) => nexthop,
+            None => {
+                println!("No nexthops for interest: {}", tlv::name::uri(&interest.name));
+                super::nack::reject_interest(table, &packet, &interest, NackReason::NoRoute);
+                return;
             }
-            res_hops.push(nexthop.clone());
-        } else {
-            // TODO: send NACK
-            println!("No nexthops for interest: {:?}", packet.data);
-            return;
-        }
+        };
 
         // Call back to forwarder pipeline
-        super::interest::on_outgoing_interest(table, packet, interest, res_hops);
+        super::interest::on_outgoing_interest(table, packet, interest, vec![nexthop]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_skips_incoming_face() {
+        let nexthops = [NextHop { face: 256, cost: 1 }, NextHop { face: 257, cost: 10 }, NextHop { face: 258, cost: 5 }];
+        assert_eq!(BestRouteStrategy::best(&nexthops, 300).map(|n| n.face), Some(256));
+        assert_eq!(BestRouteStrategy::best(&nexthops, 256).map(|n| n.face), Some(258));
+        assert!(BestRouteStrategy::best(&nexthops[..1], 256).is_none());
     }
-}
\ No newline at end of file
+}
@@ -1,23 +1,188 @@
 use std::sync::Arc;
+use std::time::Instant;
 
-use crate::{table::Table, socket::UdpPacket, tlv};
+use crate::{face::FaceScope, table::{Table, cs::CSEntry, pit::PITNode}, socket::UdpPacket, tlv};
+
+/**
+ * What to do with Data that matches no PIT entry
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UnsolicitedDataPolicy {
+    DropAll,
+    AdmitLocal,
+    AdmitNetwork,
+    AdmitAll,
+}
+
+impl UnsolicitedDataPolicy {
+    pub fn from_name(name: &str) -> Option<UnsolicitedDataPolicy> {
+        match name {
+            "drop-all" => Some(UnsolicitedDataPolicy::DropAll),
+            "admit-local" => Some(UnsolicitedDataPolicy::AdmitLocal),
+            "admit-network" => Some(UnsolicitedDataPolicy::AdmitNetwork),
+            "admit-all" => Some(UnsolicitedDataPolicy::AdmitAll),
+            _ => None,
+        }
+    }
+
+    /**
+     * Check if unsolicited Data from a face of the given scope is admitted to the content store
+     */
+    pub fn admits(&self, scope: FaceScope) -> bool {
+        match self {
+            UnsolicitedDataPolicy::DropAll => false,
+            UnsolicitedDataPolicy::AdmitLocal => scope == FaceScope::Local,
+            UnsolicitedDataPolicy::AdmitNetwork => scope == FaceScope::NonLocal,
+            UnsolicitedDataPolicy::AdmitAll => true,
+        }
+    }
+}
+
+const DATA_ELEMENTS: [tlv::Type; 5] = [
+    tlv::Type::Name,
+    tlv::Type::MetaInfo,
+    tlv::Type::Content,
+    tlv::Type::SignatureInfo,
+    tlv::Type::SignatureValue,
+];
 
 pub fn process_data(table: &mut Table, packet: Arc<UdpPacket>, p_tlo: tlv::TLO) {
+    let end = p_tlo.o + p_tlo.l as usize;
+    if end > packet.data.len() {
+        return;
+    }
+    if let Err(e) = tlv::check_critical(&packet.data[p_tlo.o..end], &DATA_ELEMENTS) {
+        println!("Invalid data, dropping: {}", e);
+        return;
+    }
+
     // Get name
     let name_tlo = tlv::vec_decode::read_tlo(&packet.data[p_tlo.o..]).unwrap(); // already checked
     let name = &packet.data[p_tlo.o+name_tlo.o..p_tlo.o+name_tlo.o+name_tlo.l as usize];
 
-    // Get PIT entry
-    let entries = table.pit.get_all_can_be_pfx(&name.to_vec());
-    if entries.len() == 0 {
-        println!("No PIT entry for data, dropping: {:?}", name);
+    // Check scope
+    if !super::scope::can_receive(name, table.faces.scope(packet.face)) {
+        println!("Scope violation, dropping data from face {}", packet.face);
         return;
     }
 
-    // Send to all downstreams for all inrecords
-    for entry in entries {
-        for in_record in &entry.borrow().in_records {
-            table.send_chan.push((packet.data.clone(), in_record.face));
+    // Freshness is judged by the cache entry, for pending Interests as well
+    let freshness = read_freshness_period(&packet.data, &p_tlo).unwrap_or(0);
+    // Only the Data TLV is cached, digests are over the same bytes everywhere
+    let cs_entry = CSEntry::new(packet.data[..end].to_vec(), freshness);
+    let fresh = cs_entry.is_fresh(Instant::now());
+
+    // Get PIT entries on the Data name
+    let mut full_name = name.to_vec();
+    let mut entries = table.pit.get_all_can_be_pfx(&full_name);
+
+    // Interests may name the Data including its implicit digest
+    // Only compute the digest if such an entry exists
+    let digest_entry = match entries.last() {
+        Some((node, strategy, name_len)) if *name_len == name.len() && has_digest_child(&node.borrow()) => {
+            let wire = &packet.data[..end];
+            let component = tlv::vec_encode::implicit_digest_component(wire);
+            let child = node.borrow().children.get(&fasthash::metro::hash64(&component)).cloned();
+            full_name.extend_from_slice(&component);
+            child.map(|child| {
+                let strategy = if child.borrow().strategy > 0 { child.borrow().strategy } else { *strategy };
+                (child, strategy, full_name.len())
+            })
+        }
+        _ => None,
+    };
+    entries.extend(digest_entry);
+
+    // Let the strategy of each entry send to matching downstreams
+    let mut satisfied = false;
+    for (entry, strategy, name_len) in entries {
+        let mut in_records = entry.borrow_mut().satisfy(name_len >= name.len(), fresh);
+        in_records.retain(|r| super::scope::can_send_data(name, table.faces.scope(r.face)));
+        if in_records.is_empty() {
+            continue;
+        }
+        satisfied = true;
+
+        let strategy = table.strategies.get(strategy);
+        strategy.after_receive_data(table, packet.clone(), entry.clone(), in_records);
+
+        // Clean up the entry once nothing is pending
+        let mut node = entry.borrow_mut();
+        if node.in_records.is_empty() {
+            // Nonces of satisfied Interests must not be used again
+            for nonce_hash in node.dead_nonces(&full_name[..name_len]) {
+                table.dnl.insert(nonce_hash);
+            }
+            node.out_records.clear();
+        }
+    }
+
+    // Cache solicited Data, and unsolicited Data if the policy admits it
+    if satisfied || table.unsolicited_policy.admits(table.faces.scope(packet.face)) {
+        table.cs.insert(name, cs_entry);
+    } else {
+        println!("No PIT entry for data, dropping: {}", tlv::name::uri(name));
+    }
+}
+
+fn has_digest_child(node: &PITNode) -> bool {
+    node.children.values().any(|c| {
+        c.borrow().name.first() == Some(&(tlv::Type::ImplicitSha256DigestComponent as u8))
+    })
+}
+
+/**
+ * Read FreshnessPeriod from the MetaInfo of a Data packet
+ * Returns None if the field is absent or cannot be decoded
+ */
+fn read_freshness_period(data: &[u8], p_tlo: &tlv::TLO) -> Option<u64> {
+    let end = p_tlo.o + p_tlo.l as usize;
+    if end > data.len() {
+        return None;
+    }
+
+    // MetaInfo follows the Name
+    let mut o = p_tlo.o;
+    while o < end {
+        let tlo = tlv::vec_decode::read_tlo(&data[o..end]).ok()?;
+        if tlo.t == tlv::Type::MetaInfo as u64 {
+            let meta_end = o + tlo.o + tlo.l as usize;
+            let mut mo = o + tlo.o;
+            while mo < meta_end.min(end) {
+                let m_tlo = tlv::vec_decode::read_tlo(&data[mo..end]).ok()?;
+                if m_tlo.t == tlv::Type::FreshnessPeriod as u64 {
+                    return tlv::vec_decode::read_nni(&data[mo+m_tlo.o..end], m_tlo.l).ok();
+                }
+                mo += m_tlo.o + m_tlo.l as usize;
+            }
+            return None;
         }
+        o += tlo.o + tlo.l as usize;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face::FaceTable;
+    use crate::table::{TableConfig, pit::PITFullPolicy};
+
+    #[test]
+    fn cached_without_trailing_bytes() {
+        let config = TableConfig { cs_capacity: 16, pit_max_size: 16, pit_full_policy: PITFullPolicy::Drop };
+        let mut table = Table::new(Arc::new(FaceTable::new()), config);
+        table.unsolicited_policy = UnsolicitedDataPolicy::AdmitAll;
+
+        // Data /a followed by padding in the same datagram
+        let wire = vec![6, 5, 7, 3, 8, 1, 97];
+        let mut data = wire.clone();
+        data.extend_from_slice(&[0, 0, 0]);
+        let p_tlo = tlv::vec_decode::read_tlo(&data).unwrap();
+        process_data(&mut table, Arc::new(UdpPacket { data, face: 256, lp: None, lost: false }), p_tlo);
+
+        let full_name = [&wire[4..], &tlv::vec_encode::implicit_digest_component(&wire)[..]].concat();
+        assert_eq!(table.cs.find(&full_name, false, false).unwrap().data, wire);
     }
-}
\ No newline at end of file
+}
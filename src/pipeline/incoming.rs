@@ -1,16 +1,27 @@
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 use crossbeam::deque::Injector;
 
+use crate::face::{FaceTable, INTERNAL_FACE_ID};
 use crate::socket::UdpPacket;
 use crate::tlv;
-use crate::table::Table;
+use crate::table::{Table, TableConfig, now_ms};
 
-pub fn thread(chan_in: Arc<Injector<Arc<UdpPacket>>>, chan_out: Arc<Injector<(Vec<u8>, SocketAddr)>>) -> std::thread::JoinHandle<()> {
+const PIT_EXPIRE_INTERVAL: u64 = 100;
+
+pub fn thread(chan_in: Arc<Injector<Arc<UdpPacket>>>, faces: Arc<FaceTable>, config: TableConfig) -> std::thread::JoinHandle<()> {
     return std::thread::spawn(move || {
-        let mut table = Table::new(chan_out);
+        let mut table = Table::new(faces, config);
+        let mut last_expire = now_ms();
         loop {
+            // Expire PIT entries and clean tables periodically
+            let now = now_ms();
+            if now - last_expire >= PIT_EXPIRE_INTERVAL {
+                expire_pit(&mut table, now);
+                table.clean();
+                last_expire = now;
+            }
+
             let steal = chan_in.steal();
             match steal {
                 crossbeam::deque::Steal::Success(packet) => {
@@ -26,17 +37,38 @@ pub fn thread(chan_in: Arc<Injector<Arc<UdpPacket>>>, chan_out: Arc<Injector<(Ve
 }
 
 fn process_packet(table: &mut Table, packet: Arc<UdpPacket>) {
-    if packet.addr.port() == 0 {
+    if packet.face == INTERNAL_FACE_ID {
         crate::mgmt::process_frame(table, packet);
         return;
     }
 
     let p_tlo = tlv::vec_decode::read_tlo(&packet.data[..]).unwrap(); // already checked
-    if p_tlo.t == tlv::Type::Interest as u64 {
-        super::interest::process_interest(table, packet, p_tlo);
-    } else if p_tlo.t == tlv::Type::Data as u64 {
+    let nack = packet.lp.as_ref().and_then(|lp| lp.nack);
+    if packet.lost {
+        if p_tlo.t == tlv::Type::Interest as u64 {
+            super::interest::process_lost_interest(table, packet, p_tlo);
+        }
+    } else if p_tlo.t == tlv::Type::Interest as u64 {
+        match nack {
+            Some(reason) => super::nack::process_nack(table, packet, p_tlo, reason),
+            None => super::interest::process_interest(table, packet, p_tlo),
+        }
+    } else if p_tlo.t == tlv::Type::Data as u64 && nack.is_none() {
         super::data::process_data(table, packet, p_tlo);
     } else {
         println!("incoming: unknown TLV type, dropping: {:?}", p_tlo.t);
     }
-}
\ No newline at end of file
+}
+
+fn expire_pit(table: &mut Table, now: u64) {
+    // Let strategies act on pending Interests before they are removed
+    for (node, strategy, name) in table.pit.get_expiring(now) {
+        for nonce_hash in node.borrow().dead_nonces(&name) {
+            table.dnl.insert(nonce_hash);
+        }
+
+        let strategy = table.strategies.get(strategy);
+        strategy.before_expire_pending_interest(table, node);
+    }
+    table.pit.expire(now);
+}
@@ -1,143 +1,319 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::Arc;
 
+use crate::face::FaceScope;
 use crate::pipeline::Interest;
 use crate::socket::{UdpPacket};
-use crate::table::pit::{InRecord, NextHop, OutRecord};
+use crate::table::fib::{FIBNode, NextHop};
+use crate::table::pit::{InRecord, OutRecord, PITFullPolicy};
 use crate::tlv;
-use crate::table::Table;
-use crate::pipeline::strategy::Strategy;
+use crate::table::{Table, now_ms};
+use crate::pipeline::nack::NackReason;
 
 pub fn process_interest(table: &mut Table, packet: Arc<UdpPacket>, p_tlo: tlv::TLO) {
-    // Get name
-    let name_tlo = tlv::vec_decode::read_tlo(&packet.data[p_tlo.o..]).unwrap(); // already checked
-    let name = &packet.data[p_tlo.o+name_tlo.o..p_tlo.o+name_tlo.o+name_tlo.l as usize];
-
     // Make Interest struct
-    let mut interest = Interest::new(name.to_vec(), p_tlo);
-
-    // Get Interest parameters
-    // TODO: forwarding hint
-    let mut o = interest.outer_tlo.o + name_tlo.o + name_tlo.l as usize;
-    while o < interest.outer_tlo.o + interest.outer_tlo.l as usize {
-        let res = tlv::vec_decode::read_tlo(&packet.data[o..]);
-        if res.is_err() {
-            println!("Failed to read TLV");
+    let mut interest = match decode_interest(&packet.data, p_tlo) {
+        Ok(interest) => interest,
+        Err(e) => {
+            println!("Invalid interest, dropping: {}", e);
             return;
         }
-        let tlo = res.unwrap();
-        let tt: tlv::Type = unsafe { ::std::mem::transmute(tlo.t) };
-        match tt {
-            tlv::Type::CanBePrefix => {
-                interest.can_be_prefix = Some(true);
-            }
-            tlv::Type::MustBeFresh => {
-                interest.must_be_fresh = Some(true);
-            }
-            tlv::Type::Nonce => {
-                let nonce = tlv::vec_decode::read_u32(&packet.data[o+tlo.o..]).unwrap();
-                interest.nonce = Some(nonce);
-            }
-            tlv::Type::InterestLifetime => {
-                let lifetime = tlv::vec_decode::read_nni(&packet.data[o+tlo.o..], tlo.l).unwrap();
-                interest.lifetime = Some(lifetime);
-            }
-            tlv::Type::HopLimit => {
-                let hop_limit = tlv::vec_decode::read_u8(&packet.data[o+tlo.o..]).unwrap();
-                interest.hop_limit = Some(hop_limit);
-            }
-            _ => {
-                // TODO: evolvability
-            }
-        }
-        o += tlo.o + tlo.l as usize;
-    }
+    };
 
-    // Check hop limit
-    match interest.hop_limit {
-        Some(hop_limit) => { if hop_limit == 0 { return; } }
-        None => {}
+    // Interests that used up their hop limit are only accepted from local faces
+    if interest.hop_limit == Some(0) && table.faces.scope(packet.face) == FaceScope::NonLocal {
+        return;
     }
-    // TODO: decrement hop limit
 
-    // TODO: localhost scope violation check
+    // Check scope
+    if !super::scope::can_receive(&interest.name, table.faces.scope(packet.face)) {
+        println!("Scope violation, dropping interest from face {}", packet.face);
+        return;
+    }
 
     // Get 64-bit nonce hash and check against dead nonce list
     let nonce = match interest.nonce {
         Some(nonce) => nonce,
         None => { return; } // we don't forward interests without a nonce
     };
-    let nonce_hash = fasthash::metro::hash64_with_seed(&name[..], nonce);
+    let nonce_hash = fasthash::metro::hash64_with_seed(&interest.name[..], nonce);
     if table.dnl.contains(nonce_hash) {
-        // TODO: onInterestLoop (send NACK)
+        let wire = &packet.data[..interest.wire_len()];
+        super::nack::send_nack(table, wire, packet.face, packet.pit_token(), NackReason::Duplicate);
         return;
     }
 
     // Create new PIT entry
-    // TODO: forwarding hint checks
     let res = table.pit.insert_or_get(&interest.name);
     match res {
-        Ok((node_ref, strategy, nexthops)) => {
-            // Todo: check nonce and duplicate bla bla
+        Ok((node_ref, strategy)) => {
+            // Check for a looping Interest
+            if node_ref.borrow().has_duplicate_nonce(nonce, packet.face) {
+                let wire = &packet.data[..interest.wire_len()];
+                super::nack::send_nack(table, wire, packet.face, packet.pit_token(), NackReason::Duplicate);
+                return;
+            }
 
             // Add in record to PIT entry
             let is_new: bool;
             {
-                let mut node = node_ref.borrow_mut();
-                is_new = node.in_records.len() == 0;
-                let entry = InRecord::new(&interest, packet.addr);
-                node.in_records.push(entry);
+                let now = now_ms();
+                is_new = !node_ref.borrow().in_records.iter().any(|r| r.expiry > now);
+                let wire = packet.data[..interest.wire_len()].to_vec();
+                let entry = InRecord::new(&interest, wire, packet.face, packet.pit_token().cloned(), now);
+                table.pit.insert_in_record(&node_ref, &interest.name, entry);
             }
 
+            // Look up the FIB, by forwarding hint if the producer region is not reached
+            let route = forwarding_hint_route(table, &interest)
+                .or_else(|| table.fib.lpm(&interest.name));
+            let nexthops = match route {
+                Some(route) => {
+                    node_ref.borrow_mut().route = Rc::downgrade(&route);
+                    let hops = route.borrow().nexthops.clone();
+                    hops
+                }
+                None => Vec::new(),
+            };
+
             // Move walk results to interest struct
             interest.strategy = Some(strategy);
             interest.nexthops = Some(nexthops);
             interest.pit_node = Some(node_ref.clone());
 
             if is_new {
-                // look up content store
-                on_cs_miss(table, packet, interest);
+                // Look up content store
+                let hit = table.cs.find(
+                    &interest.name,
+                    interest.can_be_prefix.unwrap_or(false),
+                    interest.must_be_fresh.unwrap_or(false),
+                ).map(|entry| entry.data.clone());
+
+                match hit {
+                    Some(data) => on_cs_hit(table, packet, interest, data),
+                    None => on_cs_miss(table, packet, interest),
+                }
             } else {
                 on_cs_miss(table, packet, interest);
             }
         }
+        Err(e) if e.kind() == std::io::ErrorKind::OutOfMemory => {
+            println!("PIT full ({} nodes, {} drops), dropping interest: {}", table.pit.size(), table.pit.full_drops, tlv::name::uri(&interest.name));
+            if table.pit.full_policy == PITFullPolicy::NackCongestion {
+                let wire = &packet.data[..interest.wire_len()];
+                super::nack::send_nack(table, wire, packet.face, packet.pit_token(), NackReason::Congestion);
+            }
+        }
         Err(_) => {}
     }
 }
 
-fn on_cs_miss(table: &mut Table, packet: Arc<UdpPacket>, interest: Interest) {
-    // TODO: set PIT expiry timer to the time that the last PIT in-record expires
+/**
+ * Process an Interest the link service failed to deliver upstream
+ */
+pub fn process_lost_interest(table: &mut Table, packet: Arc<UdpPacket>, p_tlo: tlv::TLO) {
+    let interest = match decode_interest(&packet.data, p_tlo) {
+        Ok(interest) => interest,
+        Err(_) => { return; }
+    };
+    let (node_ref, strategy) = match table.pit.get(&interest.name) {
+        Some(entry) => entry,
+        None => { return; }
+    };
 
-    // TODO: forwarding strategy
+    // Only report if the Interest is still pending on that upstream
+    let pending = node_ref.borrow().out_records.get(&packet.face)
+        .is_some_and(|r| Some(r.nonce) == interest.nonce && r.nack.is_none());
+    if !pending {
+        return;
+    }
 
-    // For now just use best route strategy
-    super::best_route::BestRouteStrategy::after_receive_interest(table, packet, interest);
+    let strategy = table.strategies.get(strategy);
+    strategy.after_interest_loss(table, packet.face, node_ref);
+}
+
+/**
+ * Decode an Interest starting at the beginning of data, keeping its outer TLO
+ */
+pub fn decode_interest(data: &[u8], p_tlo: tlv::TLO) -> Result<Interest, std::io::Error> {
+    let (decoded, _) = tlv::packet::Interest::decode(data)?;
+
+    let mut interest = Interest::new(decoded.name, p_tlo);
+    interest.can_be_prefix = decoded.can_be_prefix.then_some(true);
+    interest.must_be_fresh = decoded.must_be_fresh.then_some(true);
+    interest.nonce = decoded.nonce;
+    interest.lifetime = decoded.lifetime;
+    interest.hop_limit = decoded.hop_limit;
+    interest.forwarding_hint = decoded.forwarding_hint;
+    Ok(interest)
+}
+
+/**
+ * Find the route to use for an Interest with a forwarding hint
+ * Returns None if the Interest should be forwarded by its own name
+ */
+fn forwarding_hint_route(table: &Table, interest: &Interest) -> Option<Rc<RefCell<FIBNode>>> {
+    if interest.forwarding_hint.is_empty() {
+        return None;
+    }
+
+    // Inside the producer region the Interest name is routable
+    let in_region = interest.forwarding_hint.iter()
+        .any(|d| table.regions.iter().any(|r| r.starts_with(d)));
+    if in_region {
+        return None;
+    }
+
+    // Use the first delegation that has a route
+    interest.forwarding_hint.iter().find_map(|d| table.fib.lpm(d))
+}
+
+fn on_cs_hit(table: &mut Table, packet: Arc<UdpPacket>, interest: Interest, data: Vec<u8>) {
+    // The Interest is satisfied right away, so drop the in-record we added
+    let node_ref = interest.pit_node.clone().unwrap();
+    node_ref.borrow_mut().in_records.retain(|r| r.face != packet.face);
+
+    let strategy = table.strategies.get(interest.strategy.unwrap_or(0));
+    strategy.after_content_store_hit(table, packet, interest, data);
+}
+
+fn on_cs_miss(table: &mut Table, packet: Arc<UdpPacket>, interest: Interest) {
+    let strategy = table.strategies.get(interest.strategy.unwrap_or(0));
+    strategy.after_receive_interest(table, packet, interest);
 }
 
 pub fn on_outgoing_interest(table: &mut Table, packet: Arc<UdpPacket>, interest: Interest, nexthops: Vec<NextHop>) {
+    // Local faces get the hop limit as received, other faces decrement it
+    let local_wire = packet.data[..interest.wire_len()].to_vec();
+    let remote_wire = match interest.hop_limit {
+        Some(0) => None,
+        Some(hop_limit) => {
+            let res = tlv::vec_encode::replace_child(
+                &packet.data, &interest.outer_tlo, tlv::Type::HopLimit as u64, &[hop_limit - 1]);
+            match res {
+                Ok(wire) => Some(wire),
+                Err(_) => { return; }
+            }
+        }
+        None => Some(local_wire.clone()),
+    };
+
     // Insert out-records and send packets
     let node_ref = interest.pit_node.unwrap();
     let mut node = node_ref.borrow_mut();
 
+    let in_scope = table.faces.scope(packet.face);
     for nexthop in nexthops {
-        let nexthop_hash = fasthash::metro::hash64(&nexthop.addr.to_string());
-        let old_record = node.out_records.get_mut(&nexthop_hash);
+        let out_scope = table.faces.scope(nexthop.face);
+        if !super::scope::can_forward_interest(&interest.name, in_scope, out_scope) {
+            continue;
+        }
+
+        // Interests that used up their hop limit only reach local faces
+        let wire = match (out_scope, &remote_wire) {
+            (FaceScope::Local, _) => &local_wire,
+            (_, Some(wire)) => wire,
+            (_, None) => {
+                println!("Hop limit reached, not forwarding to face {}: {}", nexthop.face, tlv::name::uri(&interest.name));
+                continue;
+            }
+        };
+
+        let old_record = node.out_records.get_mut(&nexthop.face);
         match old_record {
             Some(old_record) => {
                 old_record.nonce = interest.nonce.unwrap();
-                old_record.timestamp = 0;
+                old_record.timestamp = now_ms();
+                old_record.nack = None;
             }
             None => {
                 let entry = OutRecord {
-                    face: nexthop.addr,
+                    face: nexthop.face,
                     nonce: interest.nonce.unwrap(),
-                    timestamp: 0, // TODO: get current time
+                    timestamp: now_ms(),
+                    nack: None,
                 };
-                node.out_records.insert(nexthop_hash, entry);
+                node.out_records.insert(nexthop.face, entry);
             }
         }
 
         // Send packet
-        // TODO: update nexthop field first
-        table.send_chan.push((packet.data.clone(), nexthop.addr));
+        table.send(nexthop.face, wire.clone());
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::face::{Face, FaceId, FacePersistency, FaceTable, LinkType};
+    use crate::table::TableConfig;
+
+    struct TestFace {
+        id: FaceId,
+        scope: FaceScope,
+        sent: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl Face for TestFace {
+        fn id(&self) -> FaceId { self.id }
+        fn remote_uri(&self) -> String { format!("test://{}", self.id) }
+        fn local_uri(&self) -> String { "test://".to_string() }
+        fn scope(&self) -> FaceScope { self.scope }
+        fn persistency(&self) -> FacePersistency { FacePersistency::OnDemand }
+        fn link_type(&self) -> LinkType { LinkType::PointToPoint }
+        fn mtu(&self) -> usize { tlv::MAX_NDN_PACKET_SIZE }
+        fn send(&self, packet: Vec<u8>) { self.sent.lock().unwrap().push(packet); }
+    }
+
+    // Forward Interest /a with a hop limit between faces of the given scopes, returns what was sent
+    fn forward(in_scope: FaceScope, out_scope: FaceScope, hop_limit: u8) -> Vec<Vec<u8>> {
+        let faces = Arc::new(FaceTable::new());
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let in_face = faces.get_or_add("test://in", |id| {
+            Arc::new(TestFace { id, scope: in_scope, sent: Arc::new(Mutex::new(Vec::new())) })
+        });
+        let out_face = faces.get_or_add("test://out", |id| {
+            Arc::new(TestFace { id, scope: out_scope, sent: sent.clone() })
+        });
+
+        let config = TableConfig { cs_capacity: 16, pit_max_size: 16, pit_full_policy: PITFullPolicy::Drop };
+        let mut table = Table::new(faces, config);
+        table.fib.insert_hop(&[8, 1, 97], NextHop { face: out_face, cost: 0 }).unwrap();
+
+        let data = vec![5, 14, 7, 3, 8, 1, 97, 10, 4, 1, 2, 3, 4, 34, 1, hop_limit];
+        let p_tlo = tlv::vec_decode::read_tlo(&data).unwrap();
+        process_interest(&mut table, Arc::new(UdpPacket { data, face: in_face, lp: None, lost: false }), p_tlo);
+
+        let sent = sent.lock().unwrap().clone();
+        sent
+    }
+
+    fn hop_limit_of(wire: &[u8]) -> Option<u8> {
+        tlv::packet::Interest::decode(wire).unwrap().0.hop_limit
+    }
+
+    #[test]
+    fn hop_limit_decremented_towards_non_local() {
+        let sent = forward(FaceScope::NonLocal, FaceScope::NonLocal, 2);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(hop_limit_of(&sent[0]), Some(1));
+    }
+
+    #[test]
+    fn hop_limit_kept_towards_local() {
+        let sent = forward(FaceScope::NonLocal, FaceScope::Local, 1);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(hop_limit_of(&sent[0]), Some(1));
+    }
+
+    #[test]
+    fn zero_hop_limit_only_from_local() {
+        assert!(forward(FaceScope::NonLocal, FaceScope::Local, 0).is_empty());
+        assert!(forward(FaceScope::Local, FaceScope::NonLocal, 0).is_empty());
+
+        let sent = forward(FaceScope::Local, FaceScope::Local, 0);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(hop_limit_of(&sent[0]), Some(0));
+    }
+}
@@ -1,12 +1,16 @@
 use std::{rc::Rc, cell::RefCell};
 
-use crate::{tlv, table::pit::{NextHop, PITNode}};
+use crate::{tlv, table::{fib::NextHop, pit::PITNode}};
 
 pub mod incoming;
 mod interest;
-mod strategy;
+pub mod strategy;
 mod best_route;
-mod data;
+mod multicast;
+mod asf;
+pub mod data;
+mod nack;
+pub mod scope;
 
 pub struct Interest {
     pub name: Vec<u8>,
@@ -15,6 +19,7 @@ pub struct Interest {
     pub nonce: Option<u32>,
     pub lifetime: Option<u64>,
     pub hop_limit: Option<u8>,
+    pub forwarding_hint: Vec<Vec<u8>>,
 
     // Derived fields
     pub outer_tlo: tlv::TLO,
@@ -32,6 +37,7 @@ impl Interest {
             nonce: None,
             lifetime: None,
             hop_limit: None,
+            forwarding_hint: Vec::new(),
 
             outer_tlo: o_tlo,
             strategy: None,
@@ -39,4 +45,11 @@ impl Interest {
             pit_node: None,
         }
     }
+
+    /**
+     * Length of the Interest TLV on the wire
+     */
+    pub fn wire_len(&self) -> usize {
+        self.outer_tlo.o + self.outer_tlo.l as usize
+    }
 }
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+use crate::{table::Table, socket::UdpPacket, tlv};
+use super::{strategy::Strategy, nack::NackReason, Interest};
+
+pub struct MulticastStrategy {}
+
+impl Strategy for MulticastStrategy {
+    fn after_receive_interest(&self, table: &mut Table, packet: Arc<UdpPacket>, interest: Interest) {
+        // Send to every nexthop except the incoming face
+        let res_hops: Vec<_> = interest.nexthops.as_ref().unwrap()
+            .iter()
+            .filter(|n| n.face != packet.face)
+            .copied()
+            .collect();
+
+        if res_hops.is_empty() {
+            println!("No nexthops for interest: {}", tlv::name::uri(&interest.name));
+            super::nack::reject_interest(table, &packet, &interest, NackReason::NoRoute);
+            return;
+        }
+
+        // Call back to forwarder pipeline
+        super::interest::on_outgoing_interest(table, packet, interest, res_hops);
+    }
+}
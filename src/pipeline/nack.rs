@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::{face::FaceId, table::{Table, pit::PITNode}, socket::UdpPacket, tlv::{self, lp::LpPacket}};
+use super::Interest;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(u64)]
+pub enum NackReason {
+    None        = 0,
+    Congestion  = 50,
+    Duplicate   = 100,
+    NoRoute     = 150,
+}
+
+impl NackReason {
+    pub fn from_u64(v: u64) -> NackReason {
+        match v {
+            50 => NackReason::Congestion,
+            100 => NackReason::Duplicate,
+            150 => NackReason::NoRoute,
+            _ => NackReason::None,
+        }
+    }
+}
+
+/**
+ * Wrap an Interest into an LpPacket carrying a Nack header
+ */
+pub fn encode_nack(interest: &[u8], pit_token: Option<&Vec<u8>>, reason: NackReason) -> Vec<u8> {
+    let lp = LpPacket {
+        pit_token: pit_token.cloned(),
+        nack: Some(reason as u64),
+        ..Default::default()
+    };
+    crate::link::wrap(interest.to_vec(), lp)
+}
+
+pub fn send_nack(table: &mut Table, interest: &[u8], face: FaceId, pit_token: Option<&Vec<u8>>, reason: NackReason) {
+    table.send(face, encode_nack(interest, pit_token, reason));
+}
+
+/**
+ * Send a Nack to the downstream and remove its in-record
+ */
+pub fn reject_interest(table: &mut Table, packet: &UdpPacket, interest: &Interest, reason: NackReason) {
+    send_nack(table, &packet.data[..interest.wire_len()], packet.face, packet.pit_token(), reason);
+
+    if let Some(node_ref) = &interest.pit_node {
+        node_ref.borrow_mut().in_records.retain(|r| r.face != packet.face);
+    }
+}
+
+/**
+ * Process an Interest received with a Nack header
+ */
+pub fn process_nack(table: &mut Table, packet: Arc<UdpPacket>, p_tlo: tlv::TLO, reason: u64) {
+    if p_tlo.o + p_tlo.l as usize > packet.data.len() {
+        return;
+    }
+    on_incoming_nack(table, &packet.data, packet.face, NackReason::from_u64(reason));
+}
+
+fn on_incoming_nack(table: &mut Table, fragment: &[u8], face: FaceId, reason: NackReason) {
+    let i_tlo = match tlv::vec_decode::read_tlo(fragment) {
+        Ok(tlo) if tlo.t == tlv::Type::Interest as u64 => tlo,
+        _ => { return; }
+    };
+    let interest = match super::interest::decode_interest(fragment, i_tlo) {
+        Ok(interest) => interest,
+        Err(_) => { return; }
+    };
+    let nonce = match interest.nonce {
+        Some(nonce) => nonce,
+        None => { return; }
+    };
+
+    let (node_ref, strategy) = match table.pit.get(&interest.name) {
+        Some((node_ref, strategy)) => (node_ref, strategy),
+        None => { return; }
+    };
+
+    // Record the Nack on the matching out-record
+    {
+        let mut node = node_ref.borrow_mut();
+        match node.out_records.get_mut(&face) {
+            Some(record) if record.nonce == nonce => {
+                record.nack = Some(reason as u64);
+            }
+            _ => { return; }
+        }
+    }
+
+    let strategy = table.strategies.get(strategy);
+    strategy.after_receive_nack(table, face, reason, node_ref);
+}
+
+/**
+ * Send a Nack downstream once every upstream has nacked
+ */
+pub fn forward_nack(table: &mut Table, node_ref: Rc<RefCell<PITNode>>) {
+    let mut node = node_ref.borrow_mut();
+    if node.out_records.values().any(|r| r.nack.is_none()) {
+        return;
+    }
+
+    // Send the least severe reason downstream
+    let reason = node.out_records.values()
+        .filter_map(|r| r.nack)
+        .min_by_key(|r| if *r == NackReason::None as u64 { u64::MAX } else { *r })
+        .map(NackReason::from_u64)
+        .unwrap_or(NackReason::None);
+
+    for in_record in node.reject() {
+        send_nack(table, &in_record.wire, in_record.face, in_record.pit_token.as_ref(), reason);
+    }
+}
@@ -0,0 +1,41 @@
+use crate::face::FaceScope;
+
+// /8=localhost
+const LOCALHOST: &[u8] = &[8, 9, 108, 111, 99, 97, 108, 104, 111, 115, 116];
+
+// /8=localhop
+const LOCALHOP: &[u8] = &[8, 8, 108, 111, 99, 97, 108, 104, 111, 112];
+
+fn is_local(scope: FaceScope) -> bool {
+    scope == FaceScope::Local
+}
+
+/**
+ * Check if a packet may be received from a face of the given scope
+ * Packets under /localhost must come from local faces
+ */
+pub fn can_receive(name: &[u8], scope: FaceScope) -> bool {
+    !name.starts_with(LOCALHOST) || is_local(scope)
+}
+
+/**
+ * Check if an Interest may be forwarded from in_face to out_face
+ * /localhost stays on local faces, and /localhop Interests
+ * from a non-local face may only go to local faces
+ */
+pub fn can_forward_interest(name: &[u8], in_face: FaceScope, out_face: FaceScope) -> bool {
+    if name.starts_with(LOCALHOST) {
+        return is_local(out_face);
+    }
+    if name.starts_with(LOCALHOP) {
+        return is_local(in_face) || is_local(out_face);
+    }
+    true
+}
+
+/**
+ * Check if a Data packet may be sent to a face of the given scope
+ */
+pub fn can_send_data(name: &[u8], scope: FaceScope) -> bool {
+    !name.starts_with(LOCALHOST) || is_local(scope)
+}
@@ -1,7 +1,100 @@
-use std::sync::Arc;
-use crate::{table::Table, socket::UdpPacket};
-use super::Interest;
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+use crate::{face::FaceId, table::{Table, pit::{InRecord, PITNode}}, socket::UdpPacket};
+use super::{Interest, nack::NackReason};
+
+const STRATEGY_PREFIX: &str = "/localhost/nfd/strategy/";
 
 pub trait Strategy {
-    fn after_receive_interest(table: &mut Table, packet: Arc<UdpPacket>, interest: Interest);
-}
\ No newline at end of file
+    fn after_receive_interest(&self, table: &mut Table, packet: Arc<UdpPacket>, interest: Interest);
+
+    fn after_content_store_hit(&self, table: &mut Table, packet: Arc<UdpPacket>, _interest: Interest, data: Vec<u8>) {
+        table.send(packet.face, crate::link::wrap_reply(data, packet.pit_token()));
+    }
+
+    fn after_receive_data(&self, table: &mut Table, packet: Arc<UdpPacket>, _node: Rc<RefCell<PITNode>>, in_records: Vec<InRecord>) {
+        for in_record in in_records {
+            let data = crate::link::wrap_reply(packet.data.clone(), in_record.pit_token.as_ref());
+            table.send(in_record.face, data);
+        }
+    }
+
+    fn after_receive_nack(&self, table: &mut Table, _face: FaceId, _reason: NackReason, node: Rc<RefCell<PITNode>>) {
+        super::nack::forward_nack(table, node);
+    }
+
+    fn before_expire_pending_interest(&self, _table: &mut Table, _node: Rc<RefCell<PITNode>>) {}
+
+    fn after_interest_loss(&self, _table: &mut Table, _face: FaceId, _node: Rc<RefCell<PITNode>>) {}
+}
+
+struct StrategyEntry {
+    name: String,
+    version: u64,
+    id: u64,
+    strategy: Rc<dyn Strategy>,
+}
+
+/**
+ * Registry of available strategy instances keyed by name and version
+ * PIT nodes refer to strategies by their id, with 0 meaning inherit
+ */
+pub struct StrategyRegistry {
+    entries: Vec<StrategyEntry>,
+    default: Rc<dyn Strategy>,
+}
+
+impl StrategyRegistry {
+    pub fn new() -> StrategyRegistry {
+        let best_route: Rc<dyn Strategy> = Rc::new(super::best_route::BestRouteStrategy {});
+
+        let mut registry = StrategyRegistry {
+            entries: Vec::new(),
+            default: best_route.clone(),
+        };
+        registry.register("best-route", 1, best_route);
+        registry.register("multicast", 1, Rc::new(super::multicast::MulticastStrategy {}));
+        registry.register("asf", 1, Rc::new(super::asf::AsfStrategy {}));
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, version: u64, strategy: Rc<dyn Strategy>) {
+        let uri = format!("{}{}/v={}", STRATEGY_PREFIX, name, version);
+        self.entries.push(StrategyEntry {
+            name: name.to_string(),
+            version,
+            id: fasthash::metro::hash64(uri),
+            strategy,
+        });
+    }
+
+    /**
+     * Find the id of a strategy from its name
+     * Accepts a full name or a short name, with an optional version
+     * If no version is given, the latest version is used
+     */
+    pub fn lookup(&self, uri: &str) -> Option<u64> {
+        let uri = uri.strip_prefix(STRATEGY_PREFIX).unwrap_or(uri).trim_matches('/');
+
+        let (name, version) = match uri.rsplit_once("/v=") {
+            Some((name, version)) => (name, Some(version.parse::<u64>().ok()?)),
+            None => (uri, None),
+        };
+
+        self.entries.iter()
+            .filter(|e| e.name == name && version.is_none_or(|v| v == e.version))
+            .max_by_key(|e| e.version)
+            .map(|e| e.id)
+    }
+
+    /**
+     * Get a strategy instance by id, falling back to the default strategy
+     */
+    pub fn get(&self, id: u64) -> Rc<dyn Strategy> {
+        for e in &self.entries {
+            if e.id == id {
+                return e.strategy.clone();
+            }
+        }
+        self.default.clone()
+    }
+}
@@ -1,98 +1,304 @@
 use std::io::{IoSlice, IoSliceMut};
 use std::{mem::MaybeUninit};
-use std::net::{SocketAddr, SocketAddrV4};
+use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::os::unix::prelude::AsRawFd;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use crossbeam::deque::{Injector, Worker};
-use nix::sys::socket::{MsgFlags, RecvMmsgData, SockaddrIn, RecvMsg};
+use nix::sys::socket::{MsgFlags, RecvMmsgData, RecvMsg, SockaddrStorage};
 use socket2::Socket;
 
+use crate::face::{Channel, Face, FaceId, FacePersistency, FaceScope, FaceTable, LinkType};
+use crate::link::{Fragmenter, Reliability, RELIABILITY_OVERHEAD, UDP_MTU};
+
 #[derive(Debug)]
 pub struct UdpPacket {
     pub data: Vec<u8>,
-    pub addr: std::net::SocketAddr,
+    pub face: FaceId,
+    pub lp: Option<crate::tlv::lp::LpPacket>,
+    // Sent packet that the link service gave up on
+    pub lost: bool,
+}
+
+impl UdpPacket {
+    pub fn pit_token(&self) -> Option<&Vec<u8>> {
+        self.lp.as_ref().and_then(|lp| lp.pit_token.as_ref())
+    }
+}
+
+/**
+ * Scope of a UDP face, only loopback remotes are local
+ */
+pub fn face_scope(addr: &SocketAddr) -> FaceScope {
+    if addr.ip().is_loopback() {
+        FaceScope::Local
+    } else {
+        FaceScope::NonLocal
+    }
+}
+
+/**
+ * Largest frame sent to a UDP remote
+ * Loopback is not fragmented by IP, so packets go whole
+ */
+pub fn face_mtu(addr: &SocketAddr) -> usize {
+    if addr.ip().is_loopback() {
+        crate::tlv::MAX_NDN_PACKET_SIZE
+    } else {
+        UDP_MTU
+    }
+}
+
+pub fn udp_uri(addr: &SocketAddr) -> String {
+    match addr {
+        SocketAddr::V4(_) => format!("udp4://{}", addr),
+        SocketAddr::V6(_) => format!("udp6://{}", addr),
+    }
+}
+
+/**
+ * Convert a received socket address, None for non-IP families
+ */
+fn from_sockaddr(addr: &SockaddrStorage) -> Option<SocketAddr> {
+    if let Some(v4) = addr.as_sockaddr_in() {
+        return Some(SocketAddrV4::from(*v4).into());
+    }
+    addr.as_sockaddr_in6().map(|v6| SocketAddrV6::from(*v6).into())
+}
+
+fn parse_udp_uri(uri: &str) -> Option<SocketAddr> {
+    let addr = uri.strip_prefix("udp4://")
+        .or_else(|| uri.strip_prefix("udp6://"))
+        .or_else(|| uri.strip_prefix("udp://"))?;
+    addr.parse().ok()
+}
+
+// Packets queued to a UDP listener, with the remote and whether link reliability applies
+type UdpQueue = Injector<(Vec<u8>, SocketAddr, bool)>;
+
+/**
+ * Face to a remote UDP endpoint, sending through the listener's queue
+ * Link reliability is off until enabled through management
+ */
+pub struct UdpFace {
+    id: FaceId,
+    local: SocketAddr,
+    remote: SocketAddr,
+    persistency: FacePersistency,
+    queue: Arc<UdpQueue>,
+    reliability: Arc<Mutex<Reliability>>,
+    reliable: AtomicBool,
+}
+
+impl Face for UdpFace {
+    fn id(&self) -> FaceId {
+        self.id
+    }
+
+    fn remote_uri(&self) -> String {
+        udp_uri(&self.remote)
+    }
+
+    fn local_uri(&self) -> String {
+        udp_uri(&self.local)
+    }
+
+    fn scope(&self) -> FaceScope {
+        face_scope(&self.remote)
+    }
+
+    fn persistency(&self) -> FacePersistency {
+        self.persistency
+    }
+
+    fn link_type(&self) -> LinkType {
+        LinkType::PointToPoint
+    }
+
+    fn mtu(&self) -> usize {
+        face_mtu(&self.remote)
+    }
+
+    fn send(&self, packet: Vec<u8>) {
+        self.queue.push((packet, self.remote, self.reliable.load(Ordering::Relaxed)));
+    }
+
+    fn reliability(&self) -> bool {
+        self.reliable.load(Ordering::Relaxed)
+    }
+
+    fn set_reliability(&self, enabled: bool) -> bool {
+        self.reliable.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.reliability.lock().unwrap().forget(self.remote);
+        }
+        true
+    }
+}
+
+/**
+ * UDP listening address, creates faces for remotes it hears from
+ */
+pub struct UdpChannel {
+    local: SocketAddr,
+    queue: Arc<UdpQueue>,
+    reliability: Arc<Mutex<Reliability>>,
+}
+
+impl UdpChannel {
+    pub fn new(path: &str) -> UdpChannel {
+        UdpChannel {
+            local: path.parse().unwrap(),
+            queue: Arc::new(Injector::new()),
+            reliability: Arc::new(Mutex::new(Reliability::new())),
+        }
+    }
+
+    fn make_face(&self, id: FaceId, remote: SocketAddr, persistency: FacePersistency) -> Arc<dyn Face> {
+        Arc::new(UdpFace {
+            id,
+            local: self.local,
+            remote,
+            persistency,
+            queue: self.queue.clone(),
+            reliability: self.reliability.clone(),
+            reliable: AtomicBool::new(false),
+        })
+    }
+
+    /**
+     * FaceId of a remote, creating an on-demand face on first contact
+     */
+    pub fn face(&self, faces: &FaceTable, remote: SocketAddr) -> FaceId {
+        faces.get_or_add(&udp_uri(&remote), |id| self.make_face(id, remote, FacePersistency::OnDemand))
+    }
+}
+
+impl Channel for UdpChannel {
+    fn canonize(&self, remote_uri: &str) -> Option<String> {
+        parse_udp_uri(remote_uri).map(|addr| udp_uri(&addr))
+    }
+
+    fn connect(&self, id: FaceId, remote_uri: &str, persistency: FacePersistency) -> Option<Arc<dyn Face>> {
+        let remote = parse_udp_uri(remote_uri)?;
+        if remote.is_ipv4() != self.local.is_ipv4() {
+            return None;
+        }
+        Some(self.make_face(id, remote, persistency))
+    }
 }
 
 pub fn listen_udp(
-    path: &str,
+    channel: Arc<UdpChannel>,
+    faces: Arc<FaceTable>,
     sender: Arc<Injector<Arc<UdpPacket>>>,
-    receiver: Arc<Injector<(Vec<u8>, SocketAddr)>>
 ) -> Result<(), std::io::Error> {
     println!("Starting UDP listener");
 
-    let addr: SocketAddr = path.parse().unwrap();
-    let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::DGRAM, None).unwrap();
+    let socket = socket2::Socket::new(socket2::Domain::for_address(channel.local), socket2::Type::DGRAM, None).unwrap();
+    if channel.local.is_ipv6() {
+        // IPv4 peers are served by their own listener
+        socket.set_only_v6(true).unwrap();
+    }
     socket.set_recv_buffer_size(10000 * 2000).unwrap();
     socket.set_send_buffer_size(10000 * 2000).unwrap();
     socket.set_reuse_address(true).unwrap();
-    socket.bind(&addr.into()).unwrap();
+    socket.bind(&channel.local.into()).unwrap();
     let socket_arc = Arc::new(socket);
 
-    thread_out(socket_arc.clone(), receiver.clone());
-    thread_in(socket_arc.clone(), sender.clone());
+    thread_out(socket_arc.clone(), channel.clone(), faces.clone(), sender.clone());
+    thread_in(socket_arc.clone(), channel, faces, sender.clone());
     Ok(())
 }
 
-fn thread_out(socket: Arc<Socket>, receiver: Arc<Injector<(Vec<u8>, SocketAddr)>>) {
+fn thread_out(
+    socket: Arc<Socket>,
+    channel: Arc<UdpChannel>,
+    faces: Arc<FaceTable>,
+    sender: Arc<Injector<Arc<UdpPacket>>>,
+) {
     std::thread::spawn(move || {
+        // Frames to reliable faces leave room for reliability fields
+        let mut fragmenter = Fragmenter::new(UDP_MTU);
+        let mut reliable_fragmenter = Fragmenter::new(UDP_MTU - RELIABILITY_OVERHEAD);
+
         loop {
+            let mut datas = vec![];
+
             let w = Worker::new_fifo();
-            let steal = receiver.steal_batch(&w);
-            match steal {
-                crossbeam::deque::Steal::Success(_) => {
-                    let mut msgs = vec![];
-                    let mut datas = vec![];
-                    let mut iovs = vec![];
-
-                    while let Some(v) = w.pop() {
-                        datas.push(v);
+            if let crossbeam::deque::Steal::Success(_) = channel.queue.steal_batch(&w) {
+                while let Some((data, addr, reliable)) = w.pop() {
+                    if !reliable && data.len() <= face_mtu(&addr) {
+                        datas.push((data, addr));
+                        continue;
                     }
-
-                    for i in 0..datas.len() {
-                        let iov = [IoSlice::new(&datas[i].0)];
-                        iovs.push(iov);
+                    if !reliable {
+                        datas.extend(fragmenter.fragment(data).into_iter().map(|f| (f, addr)));
+                        continue;
                     }
-
-                    for i in 0..iovs.len() {
-                        let pack_addr = datas[i].1;
-                        let addr;
-                        match pack_addr {
-                            SocketAddr::V4(v4addr) => {
-                                addr = nix::sys::socket::SockaddrIn::from(v4addr);
-                            },
-                            SocketAddr::V6(_) => {
-                                println!("IPv6 not supported yet");
-                                continue;
-                            }
-                        }
-                        msgs.push(nix::sys::socket::SendMmsgData {
-                            iov: &iovs[i],
-                            cmsgs: &[],
-                            addr: Some(addr),
-                            _lt: Default::default(),
-                        });
+                    let mut r = channel.reliability.lock().unwrap();
+                    for frame in reliable_fragmenter.fragment(data) {
+                        datas.push((r.send(frame, addr), addr));
                     }
+                }
+            }
 
-                    let res = nix::sys::socket::sendmmsg(socket.as_raw_fd(), &msgs, MsgFlags::empty());
-                    match res {
-                        Ok(_) => {}
-                        Err(e) => {
-                            println!("Error: {:?}", e);
-                        }
-                    }
+            // Retransmissions and acks, and losses reported to the pipelines
+            let (frames, lost) = channel.reliability.lock().unwrap().poll();
+            datas.extend(frames);
+            for packet in lost {
+                let face = channel.face(&faces, packet.addr);
+                sender.push(Arc::new(UdpPacket { data: packet.data, face, lp: Some(packet.lp), lost: true }));
+            }
+
+            if datas.is_empty() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                continue;
+            }
+
+            let mut msgs = vec![];
+            let mut iovs = vec![];
+            for i in 0..datas.len() {
+                let iov = [IoSlice::new(&datas[i].0)];
+                iovs.push(iov);
+            }
+
+            for i in 0..iovs.len() {
+                let pack_addr = datas[i].1;
+                if pack_addr.is_ipv4() != channel.local.is_ipv4() {
+                    println!("Cannot send to {} from {}, dropping", pack_addr, channel.local);
+                    continue;
                 }
-                crossbeam::deque::Steal::Empty => {
-                    std::thread::sleep(std::time::Duration::from_millis(1));
+                msgs.push(nix::sys::socket::SendMmsgData {
+                    iov: &iovs[i],
+                    cmsgs: &[],
+                    addr: Some(SockaddrStorage::from(pack_addr)),
+                    _lt: Default::default(),
+                });
+            }
+
+            let res = nix::sys::socket::sendmmsg(socket.as_raw_fd(), &msgs, MsgFlags::empty());
+            match res {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("Error: {:?}", e);
                 }
-                crossbeam::deque::Steal::Retry => {}
             }
         }
     });
 }
 
-fn thread_in(socket: Arc<Socket>, sender: Arc<Injector<Arc<UdpPacket>>>,) {
+fn thread_in(
+    socket: Arc<Socket>,
+    channel: Arc<UdpChannel>,
+    faces: Arc<FaceTable>,
+    sender: Arc<Injector<Arc<UdpPacket>>>,
+) {
     std::thread::spawn(move || {
-        let mut receive_buffers = [[0u8; 2000]; 100];
+        let mut reassembler = crate::link::Reassembler::new(Some(channel.reliability.clone()));
+        let mut truncated = 0u64;
+
+        let mut receive_buffers = vec![[0u8; crate::tlv::MAX_NDN_PACKET_SIZE]; 100];
         let mut receive_buffers_addrs = [MaybeUninit::uninit(); 100];
         let mut receive_buffers_bytes = [0usize; 100];
 
@@ -110,7 +316,7 @@ fn thread_in(socket: Arc<Socket>, sender: Arc<Injector<Arc<UdpPacket>>>,) {
                     })
                 }
 
-                let res: Result<Vec<RecvMsg<SockaddrIn>>, nix::errno::Errno>=
+                let res: Result<Vec<RecvMsg<SockaddrStorage>>, nix::errno::Errno>=
                     nix::sys::socket::recvmmsg(socket.as_raw_fd(), &mut msgs, MsgFlags::MSG_DONTWAIT, None);
                 match res {
                     Ok(vc) => {
@@ -120,10 +326,17 @@ fn thread_in(socket: Arc<Socket>, sender: Arc<Injector<Arc<UdpPacket>>>,) {
 
                         for i in 0..vc.len() {
                             let rr = vc[i];
-                            if rr.address.is_none() {
+                            let addr = match rr.address.as_ref().and_then(from_sockaddr) {
+                                Some(addr) => addr,
+                                None => continue,
+                            };
+                            // Datagrams larger than the buffer are cut short
+                            if rr.flags.contains(MsgFlags::MSG_TRUNC) {
+                                truncated += 1;
+                                println!("Truncated datagram from {}, dropping ({} total)", addr, truncated);
                                 continue;
                             }
-                            let addr = rr.address.unwrap();
+
                             receive_buffers_addrs[i] = MaybeUninit::new(addr);
                             receive_buffers_bytes[i] = rr.bytes;
                         }
@@ -152,12 +365,12 @@ fn thread_in(socket: Arc<Socket>, sender: Arc<Injector<Arc<UdpPacket>>>,) {
                 let data = &receive_buffers[i][..receive_buffers_bytes[i]];
 
                 let addr = unsafe { receive_buffers_addrs[i].assume_init() };
-                let packet = Arc::new(UdpPacket {
-                    data: data.to_vec(),
-                    addr: SocketAddrV4::new(addr.ip().into(), addr.port()).into(),
-                });
-
-                sender.push(packet);
+                // Reliability fields are only processed on faces that enabled it
+                let face = channel.face(&faces, addr);
+                let reliable = faces.get(face).is_some_and(|f| f.reliability());
+                if let Some(packet) = reassembler.receive(data, face, reliable.then_some(addr)) {
+                    sender.push(Arc::new(packet));
+                }
             }
         }
     });
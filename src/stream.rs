@@ -0,0 +1,146 @@
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crossbeam::deque::{Injector, Steal, Worker};
+
+use crate::face::FaceId;
+use crate::link::Reassembler;
+use crate::socket::UdpPacket;
+use crate::tlv::stream_decode::TlvDecoder;
+
+/**
+ * Connected byte stream a face runs over
+ * Reading and writing happen on separate handles to the same connection
+ */
+pub trait Stream: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> Result<Self, std::io::Error>;
+    fn shutdown(&self) -> Result<(), std::io::Error>;
+}
+
+impl Stream for TcpStream {
+    fn try_clone(&self) -> Result<Self, std::io::Error> {
+        TcpStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> Result<(), std::io::Error> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+impl Stream for UnixStream {
+    fn try_clone(&self) -> Result<Self, std::io::Error> {
+        UnixStream::try_clone(self)
+    }
+
+    fn shutdown(&self) -> Result<(), std::io::Error> {
+        UnixStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+/**
+ * Run a face over a connection: send queued packets while a reader thread receives
+ * Returns when either direction fails, the caller still owns the connection
+ */
+pub fn run_connection<S: Stream>(
+    kind: &str,
+    face: FaceId,
+    queue: &Injector<Vec<u8>>,
+    stream: &S,
+    sender: &Arc<Injector<Arc<UdpPacket>>>,
+) {
+    let closed = Arc::new(AtomicBool::new(false));
+
+    let (reader, mut writer) = match (stream.try_clone(), stream.try_clone()) {
+        (Ok(reader), Ok(writer)) => (reader, writer),
+        _ => return,
+    };
+    let reader_thread = {
+        let kind = kind.to_string();
+        let sender = sender.clone();
+        let closed = closed.clone();
+        std::thread::spawn(move || {
+            thread_in(&kind, reader, face, sender);
+            closed.store(true, Ordering::Relaxed);
+        })
+    };
+
+    let w = Worker::new_fifo();
+    while !closed.load(Ordering::Relaxed) {
+        let mut data = Vec::new();
+        if let Steal::Success(_) = queue.steal_batch(&w) {
+            while let Some(packet) = w.pop() {
+                data.extend_from_slice(&packet);
+            }
+        }
+
+        if data.is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            continue;
+        }
+
+        if let Err(e) = writer.write_all(&data) {
+            println!("{} face {} write error: {:?}", kind, face, e);
+            break;
+        }
+    }
+
+    // Wake the reader if the writer gave up first
+    let _ = stream.shutdown();
+    let _ = reader_thread.join();
+}
+
+fn thread_in(kind: &str, mut stream: impl Read, face: FaceId, sender: Arc<Injector<Arc<UdpPacket>>>) {
+    let mut decoder = TlvDecoder::new();
+    let mut reassembler = Reassembler::new(None);
+
+    loop {
+        loop {
+            match decoder.pop_tlv() {
+                Ok(Some(tlv)) => {
+                    if let Some(packet) = reassembler.receive(&tlv, face, None) {
+                        sender.push(Arc::new(packet));
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    println!("{} face {} framing error: {:?}", kind, face, e);
+                    return;
+                }
+            }
+        }
+
+        match decoder.read_from(&mut stream) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Delivers its data in one read, a face still open would read again
+    struct Once(Option<Vec<u8>>);
+
+    impl Read for Once {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+            let data = self.0.take().expect("face kept reading after a framing error");
+            buf[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn oversize_tlv_closes_face() {
+        let mut data = vec![5, 254, 0, 0, 0x27, 0x10];
+        data.extend_from_slice(&[5, 2, 7, 0]);
+        let sender = Arc::new(Injector::new());
+        thread_in("Test", Once(Some(data)), 256, sender.clone());
+        assert!(sender.is_empty());
+    }
+}
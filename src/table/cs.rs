@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+use crate::tlv::{self, vec_decode, vec_encode};
+
+pub struct CSEntry {
+    pub data: Vec<u8>,
+    pub stale_at: Instant,
+}
+
+impl CSEntry {
+    /**
+     * Data is fresh for its FreshnessPeriod (ms) after it arrives
+     */
+    pub fn new(data: Vec<u8>, freshness: u64) -> CSEntry {
+        CSEntry { data, stale_at: Instant::now() + Duration::from_millis(freshness) }
+    }
+
+    pub fn is_fresh(&self, now: Instant) -> bool {
+        now < self.stale_at
+    }
+}
+
+/**
+ * Content Store keyed by the encoded name (value of the Name TLV).
+ * Since a name is a concatenation of component TLVs, all names under a
+ * prefix share the prefix bytes and are contiguous in the map.
+ * Each pipeline has its own store, prefixes stay together as long as
+ * packets are dispatched by their first component.
+ */
+pub struct ContentStore {
+    entries: BTreeMap<Vec<u8>, CSEntry>,
+    queue: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl ContentStore {
+    pub fn new(capacity: usize) -> ContentStore {
+        ContentStore {
+            entries: BTreeMap::new(),
+            queue: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /**
+     * Insert a Data packet.
+     * Evicts the oldest entries if the store is over capacity.
+     */
+    pub fn insert(&mut self, name: &[u8], entry: CSEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.insert(name.to_vec(), entry).is_none() {
+            self.queue.push_back(name.to_vec());
+        }
+
+        while self.queue.len() > self.capacity {
+            let old = self.queue.pop_front().unwrap();
+            self.entries.remove(&old);
+        }
+    }
+
+    /**
+     * Find a matching Data packet for an Interest
+     */
+    pub fn find(&self, name: &[u8], can_be_prefix: bool, must_be_fresh: bool) -> Option<&CSEntry> {
+        let now = Instant::now();
+        let matches = |entry: &CSEntry| !must_be_fresh || entry.is_fresh(now);
+
+        // Name with implicit digest matches only that exact Data
+        if let Some((prefix, digest)) = split_digest(name) {
+            return self.entries.get(prefix)
+                .filter(|e| vec_encode::implicit_digest_component(&e.data) == digest)
+                .filter(|e| matches(e));
+        }
+
+        if !can_be_prefix {
+            return self.entries.get(name).filter(|e| matches(e));
+        }
+
+        self.entries
+            .range(name.to_vec()..)
+            .take_while(|(n, _)| n.starts_with(name))
+            .map(|(_, e)| e)
+            .find(|e| matches(e))
+    }
+}
+
+/**
+ * Split a name into its prefix and a trailing implicit digest component
+ */
+fn split_digest(name: &[u8]) -> Option<(&[u8], &[u8])> {
+    let mut o = 0;
+    while o < name.len() {
+        let tlo = vec_decode::read_tlo(&name[o..]).ok()?;
+        let end = o + tlo.o + tlo.l as usize;
+        if end == name.len() && tlo.t == tlv::Type::ImplicitSha256DigestComponent as u64 {
+            return Some((&name[..o], &name[o..]));
+        }
+        o = end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(components: &[&str]) -> Vec<u8> {
+        let mut name = Vec::new();
+        for c in components {
+            name.push(tlv::Type::GenericNameComponent as u8);
+            name.push(c.len() as u8);
+            name.extend_from_slice(c.as_bytes());
+        }
+        name
+    }
+
+    fn data(name: &[u8]) -> Vec<u8> {
+        let mut data = vec![tlv::Type::Data as u8, name.len() as u8 + 2, tlv::Type::Name as u8, name.len() as u8];
+        data.extend_from_slice(name);
+        data
+    }
+
+    fn store(names: &[Vec<u8>]) -> ContentStore {
+        let mut cs = ContentStore::new(16);
+        for n in names {
+            cs.insert(n, CSEntry::new(data(n), 1000));
+        }
+        cs
+    }
+
+    #[test]
+    fn exact_match() {
+        let cs = store(&[name(&["a", "b"])]);
+        assert_eq!(cs.find(&name(&["a", "b"]), false, false).unwrap().data, data(&name(&["a", "b"])));
+        assert!(cs.find(&name(&["a"]), false, false).is_none());
+        assert!(cs.find(&name(&["a", "b", "c"]), false, false).is_none());
+    }
+
+    #[test]
+    fn can_be_prefix() {
+        let cs = store(&[name(&["ab", "c"]), name(&["a", "b"])]);
+        assert_eq!(cs.find(&name(&["a"]), true, false).unwrap().data, data(&name(&["a", "b"])));
+
+        // /ab starts with the bytes of /a but not with its component
+        let cs = store(&[name(&["ab", "c"])]);
+        assert!(cs.find(&name(&["a"]), true, false).is_none());
+        assert!(cs.find(&name(&["ab"]), true, false).is_some());
+    }
+
+    #[test]
+    fn must_be_fresh() {
+        let mut cs = store(&[name(&["a"])]);
+        assert!(cs.find(&name(&["a"]), false, true).is_some());
+
+        cs.entries.get_mut(&name(&["a"])).unwrap().stale_at = Instant::now();
+        assert!(cs.find(&name(&["a"]), false, true).is_none());
+        assert!(cs.find(&name(&["a"]), true, true).is_none());
+        assert!(cs.find(&name(&["a"]), false, false).is_some());
+
+        // Data without FreshnessPeriod is stale right away
+        cs.insert(&name(&["b"]), CSEntry::new(data(&name(&["b"])), 0));
+        assert!(cs.find(&name(&["b"]), false, true).is_none());
+    }
+
+    #[test]
+    fn implicit_digest() {
+        let cs = store(&[name(&["a"])]);
+        let digest = vec_encode::implicit_digest_component(&data(&name(&["a"])));
+        let full_name = [name(&["a"]), digest.clone()].concat();
+        assert!(cs.find(&full_name, false, false).is_some());
+
+        let mut wrong = digest;
+        *wrong.last_mut().unwrap() ^= 1;
+        assert!(cs.find(&[name(&["a"]), wrong].concat(), false, false).is_none());
+    }
+
+    #[test]
+    fn fifo_eviction() {
+        let mut cs = ContentStore::new(2);
+        for n in ["a", "b", "c"] {
+            cs.insert(&name(&[n]), CSEntry::new(data(&name(&[n])), 1000));
+        }
+        assert!(cs.find(&name(&["a"]), false, false).is_none());
+        assert!(cs.find(&name(&["b"]), false, false).is_some());
+        assert!(cs.find(&name(&["c"]), false, false).is_some());
+
+        // Refreshing an entry keeps its place in the queue
+        cs.insert(&name(&["b"]), CSEntry::new(data(&name(&["b"])), 1000));
+        cs.insert(&name(&["d"]), CSEntry::new(data(&name(&["d"])), 1000));
+        assert!(cs.find(&name(&["b"]), false, false).is_none());
+        assert!(cs.find(&name(&["d"]), false, false).is_some());
+    }
+}
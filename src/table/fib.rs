@@ -0,0 +1,189 @@
+use std::{collections::HashMap, rc::Rc, cell::RefCell};
+use crate::{face::FaceId, tlv::vec_decode};
+use super::measurements::Measurements;
+
+#[derive(Debug, Clone, Copy)]
+pub struct NextHop {
+    pub face: FaceId,
+    pub cost: u64,
+}
+
+pub struct FIBNode {
+    pub name: Vec<u8>,
+    pub children: HashMap<u64, Rc<RefCell<FIBNode>>>,
+    pub nexthops: Vec<NextHop>,
+    pub measurements: Option<Box<Measurements>>,
+}
+
+impl FIBNode {
+    pub fn new(name: Vec<u8>) -> FIBNode {
+        FIBNode {
+            name,
+            children: HashMap::new(),
+            nexthops: Vec::new(),
+            measurements: None,
+        }
+    }
+
+    pub fn insert_hop(&mut self, hop: NextHop) {
+        // Look for existing hop
+        for h in self.nexthops.iter_mut() {
+            if h.face == hop.face {
+                h.cost = hop.cost;
+                return;
+            }
+        }
+        self.nexthops.push(hop);
+    }
+
+    /**
+     * Remove the nexthop to a face
+     * Returns true if a nexthop was removed
+     */
+    pub fn remove_hop(&mut self, face: FaceId) -> bool {
+        let len = self.nexthops.len();
+        self.nexthops.retain(|h| h.face != face);
+        self.nexthops.len() != len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.nexthops.is_empty()
+    }
+}
+
+/**
+ * Split an encoded name into its component TLVs
+ */
+fn components(name: &[u8]) -> Result<Vec<&[u8]>, std::io::Error> {
+    let mut comps = Vec::new();
+    let mut o = 0;
+    while o < name.len() {
+        let tlo = vec_decode::read_tlo(&name[o..])?;
+        let end = o + tlo.o + tlo.l as usize;
+        if end > name.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Incorrect name TLV encoding"));
+        }
+        comps.push(&name[o..end]);
+        o = end;
+    }
+    Ok(comps)
+}
+
+pub struct FIB {
+    root: Rc<RefCell<FIBNode>>,
+}
+
+impl FIB {
+    pub fn new() -> FIB {
+        FIB {
+            root: Rc::new(RefCell::new(FIBNode::new(Vec::new()))),
+        }
+    }
+
+    /**
+     * Add a nexthop to a prefix, or update its cost
+     */
+    pub fn insert_hop(&mut self, name: &[u8], hop: NextHop) -> Result<(), std::io::Error> {
+        let mut node_ref = self.root.clone();
+        for comp in components(name)? {
+            let child = node_ref.borrow_mut().children
+                .entry(fasthash::metro::hash64(comp))
+                .or_insert_with(|| Rc::new(RefCell::new(FIBNode::new(comp.to_vec()))))
+                .clone();
+            node_ref = child;
+        }
+
+        node_ref.borrow_mut().insert_hop(hop);
+        Ok(())
+    }
+
+    /**
+     * Remove a nexthop from a prefix and prune empty entries
+     * Returns true if a nexthop was removed
+     */
+    pub fn remove_hop(&mut self, name: &[u8], face: FaceId) -> Result<bool, std::io::Error> {
+        let comps = components(name)?;
+
+        // Walk down, remembering the path for pruning
+        let mut path = vec![self.root.clone()];
+        for comp in &comps {
+            let child = match path.last().unwrap().borrow().children.get(&fasthash::metro::hash64(comp)) {
+                Some(n) => n.clone(),
+                None => { return Ok(false); }
+            };
+            path.push(child);
+        }
+
+        let removed = path.last().unwrap().borrow_mut().remove_hop(face);
+
+        // Prune empty nodes bottom up
+        for i in (1..path.len()).rev() {
+            if !path[i].borrow().is_empty() {
+                break;
+            }
+            path[i-1].borrow_mut().children.remove(&fasthash::metro::hash64(comps[i-1]));
+        }
+
+        Ok(removed)
+    }
+
+    /**
+     * Find the longest prefix of a name that has nexthops
+     */
+    pub fn lpm(&self, name: &[u8]) -> Option<Rc<RefCell<FIBNode>>> {
+        let mut o = 0; // offset in name
+        let mut node_ref = self.root.clone();
+        let mut route = None;
+
+        loop {
+            node_ref = {
+                let node = node_ref.borrow();
+                if !node.nexthops.is_empty() {
+                    route = Some(node_ref.clone());
+                }
+
+                if o >= name.len() {
+                    break;
+                }
+
+                let tlo = match vec_decode::read_tlo(&name[o..]) {
+                    Ok(tlo) => tlo,
+                    Err(_) => { break; }
+                };
+                if o + tlo.o + tlo.l as usize > name.len() {
+                    break;
+                }
+                let n_name = &name[o..o+tlo.o+tlo.l as usize];
+                o += tlo.o + tlo.l as usize;
+                match node.children.get(&fasthash::metro::hash64(n_name)) {
+                    Some(n) => n.clone(),
+                    None => { break; }
+                }
+            };
+        }
+
+        route
+    }
+
+    /**
+     * List all prefixes with their nexthops
+     */
+    pub fn list(&self) -> Vec<(Vec<u8>, Vec<NextHop>)> {
+        let mut entries = Vec::new();
+        let mut stack = vec![(self.root.clone(), Vec::new())];
+
+        while let Some((node_ref, prefix)) = stack.pop() {
+            let node = node_ref.borrow();
+            if !node.nexthops.is_empty() {
+                entries.push((prefix.clone(), node.nexthops.clone()));
+            }
+            for child in node.children.values() {
+                let mut name = prefix.clone();
+                name.extend_from_slice(&child.borrow().name);
+                stack.push((child.clone(), name));
+            }
+        }
+
+        entries
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use crate::face::FaceId;
+
+/**
+ * RTT measurements of one face under a prefix
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct FaceInfo {
+    pub srtt: Option<f64>,
+    pub rttvar: f64,
+    pub last_rtt: Option<u64>,
+    pub timeouts: u64,
+    pub last_probe: Option<u64>,
+}
+
+impl FaceInfo {
+    pub fn new() -> FaceInfo {
+        FaceInfo {
+            srtt: None,
+            rttvar: 0.0,
+            last_rtt: None,
+            timeouts: 0,
+            last_probe: None,
+        }
+    }
+
+    /**
+     * Update the smoothed RTT with a new sample (RFC 6298)
+     */
+    pub fn add_rtt(&mut self, rtt: u64) {
+        let r = rtt as f64;
+        match self.srtt {
+            Some(srtt) => {
+                self.rttvar = 0.75 * self.rttvar + 0.25 * (srtt - r).abs();
+                self.srtt = Some(0.875 * srtt + 0.125 * r);
+            }
+            None => {
+                self.rttvar = r / 2.0;
+                self.srtt = Some(r);
+            }
+        }
+        self.last_rtt = Some(rtt);
+        self.timeouts = 0;
+    }
+
+    pub fn add_timeout(&mut self) {
+        self.timeouts += 1;
+    }
+
+    /**
+     * Retransmission timeout (RFC 6298), if any RTT was measured
+     */
+    pub fn rto(&self) -> Option<u64> {
+        self.srtt.map(|srtt| (srtt + 4.0 * self.rttvar) as u64)
+    }
+}
+
+/**
+ * Strategy measurements stored on a FIB entry
+ */
+pub struct Measurements {
+    pub faces: HashMap<FaceId, FaceInfo>,
+    pub last_probe: Option<u64>,
+}
+
+impl Measurements {
+    pub fn new() -> Measurements {
+        Measurements {
+            faces: HashMap::new(),
+            last_probe: None,
+        }
+    }
+
+    pub fn face(&mut self, face: FaceId) -> &mut FaceInfo {
+        self.faces.entry(face).or_insert_with(FaceInfo::new)
+    }
+}
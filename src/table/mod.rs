@@ -1,31 +1,72 @@
-use std::net::SocketAddr;
-use std::sync::Arc;
-use crossbeam::channel::Sender;
-use crossbeam::deque::Injector;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 
+use crate::face::{FaceId, FaceTable};
+use crate::pipeline::data::UnsolicitedDataPolicy;
+use crate::pipeline::strategy::StrategyRegistry;
+
+use self::cs::ContentStore;
 use self::dnl::DeadNonceList;
-use self::pit::PIT;
+use self::fib::FIB;
+use self::pit::{PIT, PITFullPolicy};
 
+pub mod cs;
 pub mod dnl;
+pub mod fib;
+pub mod measurements;
 pub mod pit;
 
 const DNL_MAX_LENGTH: usize = 4096;
+const UNSOLICITED_DATA_POLICY: UnsolicitedDataPolicy = UnsolicitedDataPolicy::DropAll;
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+/**
+ * Monotonic time in milliseconds, used for table timers
+ */
+pub fn now_ms() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/**
+ * Table limits set at startup, shared by all pipelines
+ */
+#[derive(Debug, Copy, Clone)]
+pub struct TableConfig {
+    pub cs_capacity: usize,
+    pub pit_max_size: usize,
+    pub pit_full_policy: PITFullPolicy,
+}
 
 pub struct Table {
+    pub cs: ContentStore,
     pub dnl: DeadNonceList,
+    pub fib: FIB,
     pub pit: PIT,
-    pub send_chan: Arc<Injector<(Vec<u8>, SocketAddr)>>,
+    pub strategies: StrategyRegistry,
+    pub regions: Vec<Vec<u8>>,
+    pub unsolicited_policy: UnsolicitedDataPolicy,
+    pub faces: Arc<FaceTable>,
 }
 
 impl Table {
-    pub fn new(send_chan: Arc<Injector<(Vec<u8>, SocketAddr)>>) -> Table {
+    pub fn new(faces: Arc<FaceTable>, config: TableConfig) -> Table {
         Table {
+            cs: ContentStore::new(config.cs_capacity),
             dnl: DeadNonceList::new(DNL_MAX_LENGTH),
-            pit: PIT::new(),
-            send_chan,
+            fib: FIB::new(),
+            pit: PIT::new(config.pit_max_size, config.pit_full_policy),
+            strategies: StrategyRegistry::new(),
+            regions: Vec::new(),
+            unsolicited_policy: UNSOLICITED_DATA_POLICY,
+            faces,
         }
     }
 
+    pub fn send(&self, face: FaceId, packet: Vec<u8>) {
+        self.faces.send(face, packet);
+    }
+
     pub fn clean(&mut self) {
         self.dnl.clean();
     }
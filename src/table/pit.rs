@@ -1,19 +1,16 @@
-use std::{collections::HashMap, net::SocketAddr, rc::Rc, cell::{RefCell, RefMut} };
-use crate::{pipeline::Interest, tlv::vec_decode};
+use std::{collections::{BTreeSet, HashMap, HashSet}, rc::{Rc, Weak}, cell::RefCell };
+use crate::{face::FaceId, pipeline::Interest, tlv::vec_decode};
+use super::fib::FIBNode;
 
-#[derive(Debug, Clone, Copy)]
-pub struct NextHop {
-    pub addr: SocketAddr,
-    pub cost: u64,
-}
+const DEFAULT_INTEREST_LIFETIME: u64 = 4000;
 
 pub struct PITNode {
     pub name: Vec<u8>,
     pub children: HashMap<u64, Rc<RefCell<PITNode>>>,
     pub in_records: Vec<InRecord>,
-    pub out_records: HashMap<u64, OutRecord>,
+    pub out_records: HashMap<FaceId, OutRecord>,
     pub strategy: u64,
-    pub nexthops: Vec<NextHop>,
+    pub route: Weak<RefCell<FIBNode>>,
 }
 
 impl PITNode {
@@ -24,88 +21,211 @@ impl PITNode {
             in_records: Vec::new(),
             out_records: HashMap::new(),
             strategy: 0,
-            nexthops: Vec::new(),
+            route: Weak::new(),
         }
     }
 
-    pub fn insert_hop(&mut self, hop: NextHop) {
-        // Look for existing hop
-        for i in 0..self.nexthops.len() {
-            if self.nexthops[i].addr == hop.addr {
-                self.nexthops[i].cost = hop.cost;
+    /**
+     * Insert an in-record, replacing any existing record from the same face
+     */
+    pub fn insert_in_record(&mut self, record: InRecord) {
+        for i in 0..self.in_records.len() {
+            if self.in_records[i].face == record.face {
+                self.in_records[i] = record;
                 return;
             }
         }
-        self.nexthops.push(hop);
+        self.in_records.push(record);
+    }
+
+    /**
+     * Check if a nonce was already seen from another face, or sent upstream
+     * to any face. A retransmission by the same downstream is not a loop
+     */
+    pub fn has_duplicate_nonce(&self, nonce: u32, face: FaceId) -> bool {
+        if self.in_records.iter().any(|r| r.nonce == Some(nonce) && r.face == face) {
+            return false;
+        }
+        self.in_records.iter().any(|r| r.nonce == Some(nonce) && r.face != face)
+            || self.out_records.values().any(|r| r.nonce == nonce)
+    }
+
+    /**
+     * Hashes of the nonces sent upstream, for the dead nonce list
+     */
+    pub fn dead_nonces(&self, name: &[u8]) -> Vec<u64> {
+        self.out_records.values()
+            .map(|r| fasthash::metro::hash64_with_seed(name, r.nonce))
+            .collect()
+    }
+
+    /**
+     * Remove the in-records satisfied by a Data packet
+     * exact is true if the Data name equals the node name,
+     * fresh is true if the Data can satisfy MustBeFresh
+     * Returns the removed in-records
+     */
+    pub fn satisfy(&mut self, exact: bool, fresh: bool) -> Vec<InRecord> {
+        let (satisfied, pending) = std::mem::take(&mut self.in_records)
+            .into_iter()
+            .partition(|r| {
+                (exact || r.can_be_prefix == Some(true)) && (fresh || r.must_be_fresh != Some(true))
+            });
+        self.in_records = pending;
+        satisfied
+    }
+
+    /**
+     * Remove all in-records and out-records, e.g. when the entry is nacked
+     * Returns the removed in-records
+     */
+    pub fn reject(&mut self) -> Vec<InRecord> {
+        self.out_records.clear();
+        std::mem::take(&mut self.in_records)
+    }
+
+    /**
+     * Expire records of the node at name, relative to this node
+     * Nodes on the path that are empty and not referenced elsewhere are removed,
+     * and pruned is incremented for each of them
+     */
+    fn expire_name(&mut self, name: &[u8], now: u64, pruned: &mut usize) {
+        if name.is_empty() {
+            self.in_records.retain(|r| r.expiry > now);
+            if self.in_records.is_empty() {
+                self.out_records.clear();
+            }
+            return;
+        }
+
+        let end = match vec_decode::read_tlo(name) {
+            Ok(tlo) if tlo.o + tlo.l as usize <= name.len() => tlo.o + tlo.l as usize,
+            _ => return,
+        };
+        let n_hash = fasthash::metro::hash64(&name[..end]);
+        let remove = match self.children.get(&n_hash) {
+            Some(c) => {
+                c.borrow_mut().expire_name(&name[end..], now, pruned);
+                Rc::strong_count(c) == 1 && c.borrow().is_empty()
+            }
+            None => false,
+        };
+        if remove {
+            self.children.remove(&n_hash);
+            *pruned += 1;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.children.is_empty()
+            && self.in_records.is_empty()
+            && self.strategy == 0
     }
 }
 
 #[derive(Debug)]
 pub struct InRecord {
     pub expiry: u64,
-    pub face: std::net::SocketAddr,
+    pub face: FaceId,
     pub can_be_prefix: Option<bool>,
     pub must_be_fresh: Option<bool>,
     pub nonce: Option<u32>,
     pub lifetime: Option<u64>,
     pub hop_limit: Option<u8>,
+    pub wire: Vec<u8>,
+    pub pit_token: Option<Vec<u8>>,
 }
 
 impl InRecord {
-    pub fn new(interest: &Interest, face: SocketAddr) -> InRecord {
+    pub fn new(interest: &Interest, wire: Vec<u8>, face: FaceId, pit_token: Option<Vec<u8>>, now: u64) -> InRecord {
         InRecord {
-            expiry: 0,
+            expiry: now + interest.lifetime.unwrap_or(DEFAULT_INTEREST_LIFETIME),
             face,
             can_be_prefix: interest.can_be_prefix,
             must_be_fresh: interest.must_be_fresh,
             nonce: interest.nonce,
             lifetime: interest.lifetime,
             hop_limit: interest.hop_limit,
+            wire,
+            pit_token,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct OutRecord {
-    pub face: std::net::SocketAddr,
+    pub face: FaceId,
     pub nonce: u32,
     pub timestamp: u64,
-    // nacked_field: Vec<u64>,
+    pub nack: Option<u64>,
+}
+
+/**
+ * What to do with an Interest that needs a new node when the PIT is full
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PITFullPolicy {
+    Drop,
+    NackCongestion,
+}
+
+impl PITFullPolicy {
+    pub fn from_name(name: &str) -> Option<PITFullPolicy> {
+        match name {
+            "drop" => Some(PITFullPolicy::Drop),
+            "nack-congestion" => Some(PITFullPolicy::NackCongestion),
+            _ => None,
+        }
+    }
 }
 
 pub struct PIT {
     root: Rc<RefCell<PITNode>>,
+    // Expiry time and name of in-records, so expiration does not walk the tree
+    expiries: BTreeSet<(u64, Vec<u8>)>,
+    size: usize,
+    max_size: usize,
+    pub full_policy: PITFullPolicy,
+    pub full_drops: u64,
 }
 
 impl PIT {
-    pub fn new() -> PIT {
+    pub fn new(max_size: usize, full_policy: PITFullPolicy) -> PIT {
         PIT {
             root: Rc::new(RefCell::new(PITNode::new(Vec::new()))),
+            expiries: BTreeSet::new(),
+            size: 0,
+            max_size,
+            full_policy,
+            full_drops: 0,
         }
     }
 
+    /**
+     * Number of name nodes in the PIT, excluding the root
+     */
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     /**
      * Add a name node to the PIT or get matching node
-     * Returns (node, strategy, nexthops)
+     * Returns (node, strategy)
      */
-    pub fn insert_or_get(&mut self, name: &Vec<u8>) -> Result<
-        (Rc<RefCell<PITNode>>, u64, Vec<NextHop>),
-        std::io::Error
-    >{
+    pub fn insert_or_get(&mut self, name: &Vec<u8>) -> Result<(Rc<RefCell<PITNode>>, u64), std::io::Error> {
+        let mut created = false;
+        let res = self.insert_path(name, &mut created);
+        if res.is_err() && created {
+            // Nodes made before the failure have no in-record, prune them on the next expiration
+            self.expiries.insert((0, name.clone()));
+        }
+        res
+    }
+
+    fn insert_path(&mut self, name: &[u8], created: &mut bool) -> Result<(Rc<RefCell<PITNode>>, u64), std::io::Error> {
         let mut o = 0; // offset in name
         let mut node_ref = self.root.clone();
         let mut strategy = 0;
-        let mut nexthops = Vec::new();
-
-        // Closure to get strategy and nexthops at current node
-        let mut get_strategy_nexthops = |node: &RefMut<PITNode>| {
-            if node.strategy > 0 {
-                strategy = node.strategy;
-            }
-            if node.nexthops.len() > 0 {
-                nexthops = node.nexthops.clone();
-            }
-        };
 
         // Go over entire name
         while o < name.len() {
@@ -113,7 +233,9 @@ impl PIT {
 
             node_ref = {
                 let mut node = node_ref.borrow_mut();
-                get_strategy_nexthops(&node);
+                if node.strategy > 0 {
+                    strategy = node.strategy;
+                }
 
                 if o + tlo.o + tlo.l as usize > name.len() {
                     return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Incorrect name TLV encoding"));
@@ -126,6 +248,13 @@ impl PIT {
                 match enode {
                     Some(n) => { n.clone() },
                     None => {
+                        if self.size >= self.max_size {
+                            self.full_drops += 1;
+                            return Err(std::io::Error::new(std::io::ErrorKind::OutOfMemory, "PIT is full"));
+                        }
+                        self.size += 1;
+                        *created = true;
+
                         let n = Rc::new(RefCell::new(PITNode::new(n_name.to_vec())));
                         node.children.insert(n_hash, n.clone());
                         n
@@ -137,99 +266,231 @@ impl PIT {
         }
 
         // Check leaf node as well
-        get_strategy_nexthops(&node_ref.borrow_mut());
+        if node_ref.borrow().strategy > 0 {
+            strategy = node_ref.borrow().strategy;
+        }
+
+        Ok((node_ref, strategy))
+    }
 
-        return Ok((node_ref.clone(), strategy, nexthops));
+    /**
+     * Insert an in-record into the node of name, and index its expiry
+     */
+    pub fn insert_in_record(&mut self, node: &Rc<RefCell<PITNode>>, name: &[u8], record: InRecord) {
+        self.expiries.insert((record.expiry, name.to_vec()));
+        node.borrow_mut().insert_in_record(record);
     }
 
     /**
      * Find a name node in the PIT
-     * Returns (node, strategy, nexthops)
+     * Returns (node, strategy)
      */
-    pub fn get(&mut self, name: &Vec<u8>) -> Option<(Rc<RefCell<PITNode>>, u64, Vec<NextHop>)> {
-        // INCORRECT FOR GETTING STRATEGY AND NEXTHOPS
+    pub fn get(&self, name: &Vec<u8>) -> Option<(Rc<RefCell<PITNode>>, u64)> {
         let mut o = 0; // offset in name
-        let mut node_ref_opt = Some(self.root.clone());
+        let mut node_ref = self.root.clone();
         let mut strategy = 0;
-        let mut nexthops = Vec::new();
-
-        while o < name.len() {
-            let tlo = vec_decode::read_tlo(&name[o..]);
 
-            node_ref_opt = {
-                let node_ref = match node_ref_opt {
-                    Some(n) => n,
-                    None => { return None; }
-                };
+        loop {
+            // Strategy is inherited from the longest prefix
+            node_ref = {
                 let node = node_ref.borrow();
-
                 if node.strategy > 0 {
                     strategy = node.strategy;
                 }
-                if node.nexthops.len() > 0 {
-                    nexthops = node.nexthops.clone();
+
+                if o >= name.len() {
+                    break;
                 }
 
-                match tlo {
-                    Ok(tlo) => {
-                        let n_name = &name[o..o+tlo.o+tlo.l as usize];
-                        let n_hash = fasthash::metro::hash64(n_name);
-                        let n = node.children.get(&n_hash);
-                        o += tlo.o + tlo.l as usize;
-                        match n {
-                            Some(n) => { Some(n.clone()) },
-                            None => { None }
-                        }
-                    }
-                    Err(_) => { None }
+                let tlo = vec_decode::read_tlo(&name[o..]).ok()?;
+                if o + tlo.o + tlo.l as usize > name.len() {
+                    return None;
                 }
+                let n_name = &name[o..o+tlo.o+tlo.l as usize];
+                let n_hash = fasthash::metro::hash64(n_name);
+                o += tlo.o + tlo.l as usize;
+                node.children.get(&n_hash)?.clone()
             };
         }
 
-        match node_ref_opt {
-            Some(n) => { Some((n, strategy, nexthops)) },
-            None => { None }
-        }
+        Some((node_ref, strategy))
     }
 
     /**
      * Find name nodes in the PIT that match a name including CanBePrefix higher components
-     * Returns nodes
+     * Returns (node, strategy, length of the node's name prefix)
      */
-    pub fn get_all_can_be_pfx(&mut self, name: &Vec<u8>) -> Vec<Rc<RefCell<PITNode>>> {
+    pub fn get_all_can_be_pfx(&mut self, name: &Vec<u8>) -> Vec<(Rc<RefCell<PITNode>>, u64, usize)> {
         let mut o = 0; // offset in name
-        let mut node_ref_opt = Some(self.root.clone());
+        let mut node_ref = self.root.clone();
+        let mut strategy = self.root.borrow().strategy;
         let mut nodes = Vec::new();
 
         while o < name.len() {
-            let tlo = vec_decode::read_tlo(&name[o..]);
+            let tlo = match vec_decode::read_tlo(&name[o..]) {
+                Ok(tlo) => tlo,
+                Err(_) => { return nodes; }
+            };
+            if o + tlo.o + tlo.l as usize > name.len() {
+                return nodes;
+            }
 
-            node_ref_opt = {
-                let node_ref = match node_ref_opt {
-                    Some(n) => n,
-                    None => { return nodes; }
-                };
-                let node = node_ref.borrow();
+            let n_name = &name[o..o+tlo.o+tlo.l as usize];
+            let n_hash = fasthash::metro::hash64(n_name);
+            o += tlo.o + tlo.l as usize;
 
-                match tlo {
-                    Ok(tlo) => {
-                        let n_name = &name[o..o+tlo.o+tlo.l as usize];
-                        let n_hash = fasthash::metro::hash64(n_name);
-                        let n = node.children.get(&n_hash);
-                        o += tlo.o + tlo.l as usize;
-                        match n {
-                            Some(n) => {
-                                nodes.push(n.clone());
-                                Some(n.clone())
-                            },
-                            None => { None }
-                        }
-                    }
-                    Err(_) => { None }
-                }
+            let child = match node_ref.borrow().children.get(&n_hash) {
+                Some(n) => n.clone(),
+                None => { return nodes; }
             };
+            if child.borrow().strategy > 0 {
+                strategy = child.borrow().strategy;
+            }
+            nodes.push((child.clone(), strategy, o));
+            node_ref = child;
         }
 
         nodes
     }
-}
\ No newline at end of file
+
+    /**
+     * Find nodes with pending in-records that have all expired
+     * Only names whose in-records are due in the expiry index are visited
+     * Returns (node, strategy, name)
+     */
+    pub fn get_expiring(&self, now: u64) -> Vec<(Rc<RefCell<PITNode>>, u64, Vec<u8>)> {
+        let mut seen = HashSet::new();
+        let mut nodes = Vec::new();
+        for (_, name) in self.expiries.iter().take_while(|(expiry, _)| *expiry <= now) {
+            if !seen.insert(name) {
+                continue;
+            }
+            let (node, strategy) = match self.get(name) {
+                Some(found) => found,
+                None => continue,
+            };
+            let expired = {
+                let node = node.borrow();
+                !node.in_records.is_empty() && node.in_records.iter().all(|r| r.expiry <= now)
+            };
+            if expired {
+                nodes.push((node, strategy, name.clone()));
+            }
+        }
+        nodes
+    }
+
+    /**
+     * Remove expired records and prune empty nodes
+     */
+    pub fn expire(&mut self, now: u64) {
+        let later = self.expiries.split_off(&(now + 1, Vec::new()));
+        let due = std::mem::replace(&mut self.expiries, later);
+
+        let mut pruned = 0;
+        let names: HashSet<Vec<u8>> = due.into_iter().map(|(_, name)| name).collect();
+        for name in names {
+            self.root.borrow_mut().expire_name(&name, now, &mut pruned);
+        }
+        self.size -= pruned;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_record(face: FaceId, nonce: u32) -> InRecord {
+        InRecord {
+            expiry: 0,
+            face,
+            can_be_prefix: None,
+            must_be_fresh: None,
+            nonce: Some(nonce),
+            lifetime: None,
+            hop_limit: None,
+            wire: Vec::new(),
+            pit_token: None,
+        }
+    }
+
+    fn out_record(face: FaceId, nonce: u32) -> OutRecord {
+        OutRecord { face, nonce, timestamp: 0, nack: None }
+    }
+
+    #[test]
+    fn duplicate_nonce_same_face() {
+        // A downstream retransmitting its own Interest is not a loop
+        let mut node = PITNode::new(vec![8, 1, 97]);
+        node.insert_in_record(in_record(256, 7));
+        node.out_records.insert(257, out_record(257, 7));
+        assert!(!node.has_duplicate_nonce(7, 256));
+    }
+
+    #[test]
+    fn duplicate_nonce_other_face() {
+        let mut node = PITNode::new(vec![8, 1, 97]);
+        node.insert_in_record(in_record(256, 7));
+        assert!(node.has_duplicate_nonce(7, 258));
+        assert!(!node.has_duplicate_nonce(8, 258));
+
+        // Sent upstream to face 257, then received back from 257 or from face 258
+        let mut node = PITNode::new(vec![8, 1, 97]);
+        node.out_records.insert(257, out_record(257, 9));
+        assert!(node.has_duplicate_nonce(9, 258));
+        assert!(node.has_duplicate_nonce(9, 257));
+    }
+
+    fn name(components: &[&str]) -> Vec<u8> {
+        components.iter().flat_map(|c| [&[8, c.len() as u8][..], c.as_bytes()].concat()).collect()
+    }
+
+    fn expiring(pit: &PIT, now: u64) -> Vec<Vec<u8>> {
+        pit.get_expiring(now).into_iter().map(|(_, _, name)| name).collect()
+    }
+
+    #[test]
+    fn expire_by_index() {
+        let mut pit = PIT::new(16, PITFullPolicy::Drop);
+        let ab = name(&["a", "b"]);
+        let (node, _) = pit.insert_or_get(&ab).unwrap();
+        pit.insert_in_record(&node, &ab, InRecord { expiry: 100, ..in_record(256, 1) });
+        pit.insert_in_record(&node, &ab, InRecord { expiry: 200, ..in_record(257, 2) });
+        drop(node);
+
+        // Only nodes whose in-records all expired are reported
+        assert!(expiring(&pit, 50).is_empty());
+        assert!(expiring(&pit, 100).is_empty());
+        pit.expire(100);
+        assert_eq!(pit.get(&ab).unwrap().0.borrow().in_records.len(), 1);
+
+        assert_eq!(expiring(&pit, 200), vec![ab.clone()]);
+        pit.expire(200);
+        assert!(pit.get(&ab).is_none());
+        assert_eq!(pit.size(), 0);
+    }
+
+    #[test]
+    fn refreshed_record_not_expired() {
+        let mut pit = PIT::new(16, PITFullPolicy::Drop);
+        let a = name(&["a"]);
+        let (node, _) = pit.insert_or_get(&a).unwrap();
+        pit.insert_in_record(&node, &a, InRecord { expiry: 100, ..in_record(256, 1) });
+        pit.insert_in_record(&node, &a, InRecord { expiry: 300, ..in_record(256, 1) });
+
+        assert!(expiring(&pit, 100).is_empty());
+        pit.expire(100);
+        assert_eq!(node.borrow().in_records.len(), 1);
+        assert_eq!(expiring(&pit, 300), vec![a]);
+    }
+
+    #[test]
+    fn failed_insert_pruned() {
+        let mut pit = PIT::new(1, PITFullPolicy::Drop);
+        let ab = name(&["a", "b"]);
+        assert!(pit.insert_or_get(&ab).is_err());
+        assert_eq!(pit.size(), 1);
+        pit.expire(0);
+        assert_eq!(pit.size(), 0);
+    }
+}
@@ -0,0 +1,226 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, Weak};
+use crossbeam::deque::{Injector, Steal};
+
+use crate::face::{Channel, Face, FaceId, FacePersistency, FaceScope, FaceTable, LinkType};
+use crate::socket::{face_scope, UdpPacket};
+
+// Time allowed to establish an outgoing connection (ms)
+const TCP_CONNECT_TIMEOUT: u64 = 3000;
+
+// Delay before reconnecting a persistent face, doubled up to the max (ms)
+const TCP_RECONNECT_MIN: u64 = 1000;
+const TCP_RECONNECT_MAX: u64 = 60000;
+
+pub fn tcp_uri(addr: &SocketAddr) -> String {
+    match addr {
+        SocketAddr::V4(_) => format!("tcp4://{}", addr),
+        SocketAddr::V6(_) => format!("tcp6://{}", addr),
+    }
+}
+
+fn parse_tcp_uri(uri: &str) -> Option<SocketAddr> {
+    let addr = uri.strip_prefix("tcp4://")
+        .or_else(|| uri.strip_prefix("tcp6://"))
+        .or_else(|| uri.strip_prefix("tcp://"))?;
+    addr.parse().ok()
+}
+
+/**
+ * Face over a TCP connection, with its own send queue
+ * Persistent faces keep their FaceId and reconnect when the connection fails
+ */
+pub struct TcpFace {
+    id: FaceId,
+    local: Mutex<SocketAddr>,
+    remote: SocketAddr,
+    persistency: FacePersistency,
+    queue: Injector<Vec<u8>>,
+}
+
+impl Face for TcpFace {
+    fn id(&self) -> FaceId {
+        self.id
+    }
+
+    fn remote_uri(&self) -> String {
+        tcp_uri(&self.remote)
+    }
+
+    fn local_uri(&self) -> String {
+        tcp_uri(&self.local.lock().unwrap())
+    }
+
+    fn scope(&self) -> FaceScope {
+        face_scope(&self.remote)
+    }
+
+    fn persistency(&self) -> FacePersistency {
+        self.persistency
+    }
+
+    fn link_type(&self) -> LinkType {
+        LinkType::PointToPoint
+    }
+
+    fn mtu(&self) -> usize {
+        crate::tlv::MAX_NDN_PACKET_SIZE
+    }
+
+    fn send(&self, packet: Vec<u8>) {
+        self.queue.push(packet);
+    }
+}
+
+/**
+ * TCP listening address, accepts connections and makes outgoing faces
+ */
+pub struct TcpChannel {
+    local: SocketAddr,
+    faces: Weak<FaceTable>,
+    sender: Arc<Injector<Arc<UdpPacket>>>,
+}
+
+impl TcpChannel {
+    pub fn new(path: &str, faces: Weak<FaceTable>, sender: Arc<Injector<Arc<UdpPacket>>>) -> TcpChannel {
+        TcpChannel {
+            local: path.parse().unwrap(),
+            faces,
+            sender,
+        }
+    }
+
+    fn make_face(&self, id: FaceId, local: SocketAddr, remote: SocketAddr, persistency: FacePersistency) -> Arc<TcpFace> {
+        Arc::new(TcpFace {
+            id,
+            local: Mutex::new(local),
+            remote,
+            persistency,
+            queue: Injector::new(),
+        })
+    }
+
+    fn start(&self, face: Arc<TcpFace>, stream: Option<TcpStream>) {
+        let faces = self.faces.clone();
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            thread_face(face, stream, faces, sender);
+        });
+    }
+}
+
+impl Channel for TcpChannel {
+    fn canonize(&self, remote_uri: &str) -> Option<String> {
+        parse_tcp_uri(remote_uri).map(|addr| tcp_uri(&addr))
+    }
+
+    fn connect(&self, id: FaceId, remote_uri: &str, persistency: FacePersistency) -> Option<Arc<dyn Face>> {
+        let remote = parse_tcp_uri(remote_uri)?;
+        if remote.is_ipv4() != self.local.is_ipv4() {
+            return None;
+        }
+
+        // Connecting happens on the face thread, packets queue meanwhile
+        let face = self.make_face(id, self.local, remote, persistency);
+        self.start(face.clone(), None);
+        Some(face)
+    }
+}
+
+pub fn listen_tcp(channel: Arc<TcpChannel>) -> Result<(), std::io::Error> {
+    println!("Starting TCP listener on {}", channel.local);
+
+    let socket = socket2::Socket::new(socket2::Domain::for_address(channel.local), socket2::Type::STREAM, None)?;
+    if channel.local.is_ipv6() {
+        // IPv4 peers are served by their own listener
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.bind(&channel.local.into())?;
+    socket.listen(128)?;
+    let listener: TcpListener = socket.into();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    println!("TCP accept error: {:?}", e);
+                    continue;
+                }
+            };
+            let (remote, local) = match (stream.peer_addr(), stream.local_addr()) {
+                (Ok(remote), Ok(local)) => (remote, local),
+                _ => continue,
+            };
+            let faces = match channel.faces.upgrade() {
+                Some(faces) => faces,
+                None => return,
+            };
+
+            let mut created = None;
+            faces.get_or_add(&tcp_uri(&remote), |id| {
+                let face = channel.make_face(id, local, remote, FacePersistency::OnDemand);
+                created = Some(face.clone());
+                face
+            });
+            match created {
+                Some(face) => channel.start(face, Some(stream)),
+                None => println!("TCP face to {} already exists, closing", remote),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn connect(remote: SocketAddr) -> Result<TcpStream, std::io::Error> {
+    let timeout = std::time::Duration::from_millis(TCP_CONNECT_TIMEOUT);
+    TcpStream::connect_timeout(&remote, timeout)
+}
+
+/**
+ * Run a face: send queued packets while a reader thread receives
+ * When the connection fails, on-demand faces are removed and others reconnect
+ */
+fn thread_face(
+    face: Arc<TcpFace>,
+    mut stream: Option<TcpStream>,
+    faces: Weak<FaceTable>,
+    sender: Arc<Injector<Arc<UdpPacket>>>,
+) {
+    let mut backoff = TCP_RECONNECT_MIN;
+    loop {
+        let conn = match stream.take() {
+            Some(conn) => Ok(conn),
+            None => connect(face.remote),
+        };
+
+        match conn {
+            Ok(conn) => {
+                println!("TCP face {} connected to {}", face.id, face.remote);
+                if let Ok(local) = conn.local_addr() {
+                    *face.local.lock().unwrap() = local;
+                }
+                backoff = TCP_RECONNECT_MIN;
+                let _ = conn.set_nodelay(true);
+                crate::stream::run_connection("TCP", face.id, &face.queue, &conn, &sender);
+                println!("TCP face {} to {} closed", face.id, face.remote);
+            }
+            Err(e) => {
+                println!("TCP face {} cannot connect to {}: {:?}", face.id, face.remote, e);
+            }
+        }
+
+        if face.persistency == FacePersistency::OnDemand {
+            if let Some(faces) = faces.upgrade() {
+                faces.remove(face.id);
+            }
+            return;
+        }
+
+        // Packets sent while down are dropped rather than piling up
+        std::thread::sleep(std::time::Duration::from_millis(backoff));
+        while let Steal::Success(_) | Steal::Retry = face.queue.steal() {}
+        backoff = (backoff * 2).min(TCP_RECONNECT_MAX);
+    }
+}
@@ -0,0 +1,215 @@
+/*!
+ * NDNLPv2 link protocol packets
+ * https://redmine.named-data.net/projects/nfd/wiki/NDNLPv2
+ */
+
+use super::{Type, vec_decode, vec_encode};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LpPacket {
+    pub sequence: Option<u64>,
+    pub frag_index: Option<u64>,
+    pub frag_count: Option<u64>,
+    pub pit_token: Option<Vec<u8>>,
+    /** Nack reason, 0 if the Nack carries no reason */
+    pub nack: Option<u64>,
+    pub next_hop_face_id: Option<u64>,
+    pub incoming_face_id: Option<u64>,
+    pub congestion_mark: Option<u64>,
+    pub acks: Vec<u64>,
+    pub tx_sequence: Option<u64>,
+    pub fragment: Option<Vec<u8>>,
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/**
+ * Header fields in [800, 959] with the two low bits clear may be ignored
+ */
+fn can_ignore(t: u64) -> bool {
+    (800..=959).contains(&t) && t & 0x03 == 0
+}
+
+fn read_nni(v: &[u8]) -> Result<u64, std::io::Error> {
+    vec_decode::read_nni(v, v.len() as u64)
+}
+
+fn read_nack_reason(v: &[u8]) -> Result<u64, std::io::Error> {
+    let mut o = 0;
+    while o < v.len() {
+        let tlo = vec_decode::read_tlo(&v[o..])?;
+        let end = o + tlo.o + tlo.l as usize;
+        if end > v.len() {
+            return Err(invalid("Incorrect Nack encoding"));
+        }
+        if tlo.t == Type::NackReason as u64 {
+            return read_nni(&v[o+tlo.o..end]);
+        }
+        o = end;
+    }
+    Ok(0)
+}
+
+impl LpPacket {
+    /**
+     * Make an LpPacket carrying a network layer packet
+     */
+    pub fn new(fragment: Vec<u8>) -> LpPacket {
+        LpPacket { fragment: Some(fragment), ..Default::default() }
+    }
+
+    /**
+     * Check if the packet only carries fragmentation and reliability fields
+     * Such packets can be sent as a bare network layer packet instead
+     */
+    pub fn is_bare(&self) -> bool {
+        self.pit_token.is_none()
+            && self.nack.is_none()
+            && self.next_hop_face_id.is_none()
+            && self.incoming_face_id.is_none()
+            && self.congestion_mark.is_none()
+    }
+
+    /**
+     * Decode an LpPacket TLV at the start of vec
+     * Returns the packet and the length of its encoding
+     */
+    pub fn decode(vec: &[u8]) -> Result<(LpPacket, usize), std::io::Error> {
+        let outer = vec_decode::read_tlo(vec)?;
+        if outer.t != Type::LpPacket as u64 {
+            return Err(invalid("Not an LpPacket"));
+        }
+        let end = outer.o + outer.l as usize;
+        if end > vec.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Unexpected EOF"));
+        }
+
+        let mut lp = LpPacket::default();
+        let mut o = outer.o;
+        while o < end {
+            let tlo = vec_decode::read_tlo(&vec[o..end])?;
+            let v_end = o + tlo.o + tlo.l as usize;
+            if v_end > end {
+                return Err(invalid("Incorrect LpPacket encoding"));
+            }
+            if lp.fragment.is_some() {
+                return Err(invalid("LpPacket field after Fragment"));
+            }
+
+            let v = &vec[o+tlo.o..v_end];
+            match Type::from_u64(tlo.t) {
+                Some(Type::Sequence) => lp.sequence = Some(read_nni(v)?),
+                Some(Type::FragIndex) => lp.frag_index = Some(read_nni(v)?),
+                Some(Type::FragCount) => lp.frag_count = Some(read_nni(v)?),
+                Some(Type::PitToken) => lp.pit_token = Some(v.to_vec()),
+                Some(Type::Nack) => lp.nack = Some(read_nack_reason(v)?),
+                Some(Type::NextHopFaceId) => lp.next_hop_face_id = Some(read_nni(v)?),
+                Some(Type::IncomingFaceId) => lp.incoming_face_id = Some(read_nni(v)?),
+                Some(Type::CongestionMark) => lp.congestion_mark = Some(read_nni(v)?),
+                Some(Type::Ack) => lp.acks.push(read_nni(v)?),
+                Some(Type::TxSequence) => lp.tx_sequence = Some(read_nni(v)?),
+                Some(Type::Fragment) => lp.fragment = Some(v.to_vec()),
+                _ if can_ignore(tlo.t) => {}
+                _ => { return Err(invalid("Unrecognized LpPacket field")); }
+            }
+            o = v_end;
+        }
+
+        Ok((lp, end))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut inner = Vec::new();
+        if let Some(sequence) = self.sequence {
+            vec_encode::write_tlv(&mut inner, Type::Sequence as u64, &sequence.to_be_bytes());
+        }
+        if let Some(frag_index) = self.frag_index {
+            vec_encode::write_nni(&mut inner, Type::FragIndex as u64, frag_index);
+        }
+        if let Some(frag_count) = self.frag_count {
+            vec_encode::write_nni(&mut inner, Type::FragCount as u64, frag_count);
+        }
+        if let Some(pit_token) = &self.pit_token {
+            vec_encode::write_tlv(&mut inner, Type::PitToken as u64, pit_token);
+        }
+        if let Some(reason) = self.nack {
+            let mut nack = Vec::new();
+            if reason != 0 {
+                vec_encode::write_nni(&mut nack, Type::NackReason as u64, reason);
+            }
+            vec_encode::write_tlv(&mut inner, Type::Nack as u64, &nack);
+        }
+        if let Some(face_id) = self.next_hop_face_id {
+            vec_encode::write_nni(&mut inner, Type::NextHopFaceId as u64, face_id);
+        }
+        if let Some(face_id) = self.incoming_face_id {
+            vec_encode::write_nni(&mut inner, Type::IncomingFaceId as u64, face_id);
+        }
+        if let Some(mark) = self.congestion_mark {
+            vec_encode::write_nni(&mut inner, Type::CongestionMark as u64, mark);
+        }
+        for ack in &self.acks {
+            vec_encode::write_tlv(&mut inner, Type::Ack as u64, &ack.to_be_bytes());
+        }
+        if let Some(tx_sequence) = self.tx_sequence {
+            vec_encode::write_tlv(&mut inner, Type::TxSequence as u64, &tx_sequence.to_be_bytes());
+        }
+        if let Some(fragment) = &self.fragment {
+            vec_encode::write_tlv(&mut inner, Type::Fragment as u64, fragment);
+        }
+
+        let mut out = Vec::with_capacity(inner.len() + 4);
+        vec_encode::write_tlv(&mut out, Type::LpPacket as u64, &inner);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let lp = LpPacket {
+            sequence: Some(0x0102030405060708),
+            frag_index: Some(1),
+            frag_count: Some(3),
+            pit_token: Some(vec![0xab; 8]),
+            nack: Some(150),
+            next_hop_face_id: Some(300),
+            incoming_face_id: Some(1),
+            congestion_mark: Some(1),
+            acks: vec![5, 6],
+            tx_sequence: Some(7),
+            fragment: Some(vec![0x05, 0x00]),
+        };
+        let wire = lp.encode();
+        assert_eq!(LpPacket::decode(&wire).unwrap(), (lp, wire.len()));
+    }
+
+    #[test]
+    fn nack_without_reason() {
+        let mut lp = LpPacket::new(vec![0x05, 0x00]);
+        lp.nack = Some(0);
+        let wire = lp.encode();
+        assert_eq!(wire, [0x64, 0x08, 0xfd, 0x03, 0x20, 0x00, 0x50, 0x02, 0x05, 0x00]);
+        assert_eq!(LpPacket::decode(&wire).unwrap().0, lp);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        // CachePolicy (820) may be ignored
+        let wire = [0x64, 0x08, 0xfd, 0x03, 0x34, 0x00, 0x50, 0x02, 0x05, 0x00];
+        assert_eq!(LpPacket::decode(&wire).unwrap().0, LpPacket::new(vec![0x05, 0x00]));
+
+        // Unknown field 821 may not
+        let wire = [0x64, 0x08, 0xfd, 0x03, 0x35, 0x00, 0x50, 0x02, 0x05, 0x00];
+        assert!(LpPacket::decode(&wire).is_err());
+
+        // Fields after the fragment are invalid
+        let wire = [0x64, 0x07, 0x50, 0x02, 0x05, 0x00, 0x62, 0x01, 0x00];
+        assert!(LpPacket::decode(&wire).is_err());
+    }
+}
@@ -1,5 +1,13 @@
 pub mod vec_decode;
+pub mod vec_encode;
 pub mod varnumber;
+pub mod packet;
+pub mod name;
+pub mod lp;
+pub mod stream_decode;
+
+// Maximum size of a network layer packet
+pub const MAX_NDN_PACKET_SIZE: usize = 8800;
 
 #[derive(Debug)]
 pub struct TLO {
@@ -19,9 +27,13 @@ pub enum Type {
     GenericNameComponent            = 8,
     ImplicitSha256DigestComponent   = 1,
     ParametersSha256DigestComponent = 2,
+    KeywordNameComponent            = 32,
+    SegmentNameComponent            = 50,
+    VersionNameComponent            = 54,
     CanBePrefix                     = 33,
     MustBeFresh                     = 18,
     ForwardingHint                  = 30,
+    Delegation                      = 31,
     Nonce                           = 10,
     InterestLifetime                = 12,
     HopLimit                        = 34,
@@ -41,4 +53,100 @@ pub enum Type {
     SignatureNonce                  = 38,
     SignatureTime                   = 40,
     SignatureSeqNum                 = 42,
+    LpPacket                        = 100,
+    Fragment                        = 80,
+    Sequence                        = 81,
+    FragIndex                       = 82,
+    FragCount                       = 83,
+    PitToken                        = 98,
+    Nack                            = 800,
+    NackReason                      = 801,
+    NextHopFaceId                   = 816,
+    IncomingFaceId                  = 817,
+    CongestionMark                  = 832,
+    Ack                             = 836,
+    TxSequence                      = 840,
+}
+
+impl Type {
+    pub fn from_u64(t: u64) -> Option<Type> {
+        match t {
+            0 => Some(Type::Invalid),
+            5 => Some(Type::Interest),
+            6 => Some(Type::Data),
+            7 => Some(Type::Name),
+            8 => Some(Type::GenericNameComponent),
+            1 => Some(Type::ImplicitSha256DigestComponent),
+            2 => Some(Type::ParametersSha256DigestComponent),
+            32 => Some(Type::KeywordNameComponent),
+            50 => Some(Type::SegmentNameComponent),
+            54 => Some(Type::VersionNameComponent),
+            33 => Some(Type::CanBePrefix),
+            18 => Some(Type::MustBeFresh),
+            30 => Some(Type::ForwardingHint),
+            31 => Some(Type::Delegation),
+            10 => Some(Type::Nonce),
+            12 => Some(Type::InterestLifetime),
+            34 => Some(Type::HopLimit),
+            36 => Some(Type::ApplicationParameters),
+            44 => Some(Type::InterestSignatureInfo),
+            46 => Some(Type::InterestSignatureValue),
+            20 => Some(Type::MetaInfo),
+            21 => Some(Type::Content),
+            22 => Some(Type::SignatureInfo),
+            23 => Some(Type::SignatureValue),
+            24 => Some(Type::ContentType),
+            25 => Some(Type::FreshnessPeriod),
+            26 => Some(Type::FinalBlockId),
+            27 => Some(Type::SignatureType),
+            28 => Some(Type::KeyLocator),
+            29 => Some(Type::KeyDigest),
+            38 => Some(Type::SignatureNonce),
+            40 => Some(Type::SignatureTime),
+            42 => Some(Type::SignatureSeqNum),
+            100 => Some(Type::LpPacket),
+            80 => Some(Type::Fragment),
+            81 => Some(Type::Sequence),
+            82 => Some(Type::FragIndex),
+            83 => Some(Type::FragCount),
+            98 => Some(Type::PitToken),
+            800 => Some(Type::Nack),
+            801 => Some(Type::NackReason),
+            816 => Some(Type::NextHopFaceId),
+            817 => Some(Type::IncomingFaceId),
+            832 => Some(Type::CongestionMark),
+            836 => Some(Type::Ack),
+            840 => Some(Type::TxSequence),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Types below 32 and odd types are critical (NDN Packet Format v0.3)
+ */
+pub fn is_critical(t: u64) -> bool {
+    t < 32 || t & 1 == 1
+}
+
+/**
+ * Check the children of a TLV value against the evolvability rules
+ * Unrecognized critical types make the element invalid, other unrecognized
+ * types are ignored
+ */
+pub fn check_critical(vec: &[u8], known: &[Type]) -> Result<(), std::io::Error> {
+    let mut o = 0;
+    while o < vec.len() {
+        let tlo = vec_decode::read_tlo(&vec[o..])?;
+        let end = o + tlo.o + tlo.l as usize;
+        if end > vec.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Incorrect TLV encoding"));
+        }
+        let recognized = Type::from_u64(tlo.t).is_some_and(|t| known.contains(&t));
+        if !recognized && is_critical(tlo.t) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Unrecognized critical TLV"));
+        }
+        o = end;
+    }
+    Ok(())
 }
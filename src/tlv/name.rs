@@ -0,0 +1,385 @@
+/*!
+ * Names and name components with NDN URI formatting and canonical ordering
+ * The forwarder tables keep names as encoded bytes; these types are for
+ * building names, parsing them from text and printing them in logs.
+ */
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Type, vec_decode, vec_encode};
+
+const SHA256_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Component {
+    pub t: u64,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Name {
+    pub components: Vec<Component>,
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/**
+ * Encode a number as the shortest NonNegativeInteger
+ */
+fn nni_bytes(n: u64) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    if n <= u8::MAX as u64 {
+        bytes[7..].to_vec()
+    } else if n <= u16::MAX as u64 {
+        bytes[6..].to_vec()
+    } else if n <= u32::MAX as u64 {
+        bytes[4..].to_vec()
+    } else {
+        bytes.to_vec()
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter, value: &[u8]) -> fmt::Result {
+    for b in value {
+        write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+}
+
+fn read_hex(s: &str) -> Result<Vec<u8>, std::io::Error> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(invalid("Invalid hex string"));
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i+2], 16).map_err(|_| invalid("Invalid hex string")))
+        .collect()
+}
+
+/**
+ * Percent-encode everything but unreserved characters (RFC 3986)
+ */
+fn write_escaped(f: &mut fmt::Formatter, value: &[u8]) -> fmt::Result {
+    for b in value {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(b) {
+            write!(f, "{}", *b as char)?;
+        } else {
+            write!(f, "%{:02X}", b)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_escaped(s: &str) -> Result<Vec<u8>, std::io::Error> {
+    let s = s.as_bytes();
+    let mut value = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'%' {
+            let hex = s.get(i+1..i+3).ok_or_else(|| invalid("Invalid percent encoding"))?;
+            let hex = std::str::from_utf8(hex).map_err(|_| invalid("Invalid percent encoding"))?;
+            value.push(u8::from_str_radix(hex, 16).map_err(|_| invalid("Invalid percent encoding"))?);
+            i += 3;
+        } else {
+            value.push(s[i]);
+            i += 1;
+        }
+    }
+    Ok(value)
+}
+
+/**
+ * Check the type and value length of a component
+ */
+fn check_type(t: u64, len: usize) -> Result<(), std::io::Error> {
+    if t == Type::Invalid as u64 || t > u16::MAX as u64 {
+        return Err(invalid("Invalid name component type"));
+    }
+
+    let is_digest = t == Type::ImplicitSha256DigestComponent as u64
+        || t == Type::ParametersSha256DigestComponent as u64;
+    if is_digest && len != SHA256_LENGTH {
+        return Err(invalid("Invalid digest component length"));
+    }
+    Ok(())
+}
+
+impl Component {
+    pub fn new(t: u64, value: Vec<u8>) -> Component {
+        Component { t, value }
+    }
+
+    pub fn generic(value: &[u8]) -> Component {
+        Component::new(Type::GenericNameComponent as u64, value.to_vec())
+    }
+
+    pub fn keyword(value: &[u8]) -> Component {
+        Component::new(Type::KeywordNameComponent as u64, value.to_vec())
+    }
+
+    pub fn implicit_digest(digest: &[u8]) -> Component {
+        Component::new(Type::ImplicitSha256DigestComponent as u64, digest.to_vec())
+    }
+
+    pub fn segment(n: u64) -> Component {
+        Component::new(Type::SegmentNameComponent as u64, nni_bytes(n))
+    }
+
+    pub fn version(n: u64) -> Component {
+        Component::new(Type::VersionNameComponent as u64, nni_bytes(n))
+    }
+
+    fn as_number(&self, t: Type) -> Option<u64> {
+        if self.t != t as u64 || !matches!(self.value.len(), 1 | 2 | 4 | 8) {
+            return None;
+        }
+        vec_decode::read_nni(&self.value, self.value.len() as u64).ok()
+    }
+
+    pub fn as_segment(&self) -> Option<u64> {
+        self.as_number(Type::SegmentNameComponent)
+    }
+
+    pub fn as_version(&self) -> Option<u64> {
+        self.as_number(Type::VersionNameComponent)
+    }
+
+    /**
+     * Decode a component TLV at the start of vec
+     * Returns the component and the length of its encoding
+     */
+    pub fn decode(vec: &[u8]) -> Result<(Component, usize), std::io::Error> {
+        let tlo = vec_decode::read_tlo(vec)?;
+        let end = tlo.o + tlo.l as usize;
+        if end > vec.len() {
+            return Err(invalid("Incorrect name component TLV encoding"));
+        }
+        check_type(tlo.t, tlo.l as usize)?;
+        Ok((Component::new(tlo.t, vec[tlo.o..end].to_vec()), end))
+    }
+
+    pub fn encode(&self, vec: &mut Vec<u8>) {
+        vec_encode::write_tlv(vec, self.t, &self.value);
+    }
+
+}
+
+/**
+ * Canonical order: by type, then by length, then by value
+ */
+impl Ord for Component {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.t.cmp(&other.t)
+            .then(self.value.len().cmp(&other.value.len()))
+            .then(self.value.cmp(&other.value))
+    }
+}
+
+impl PartialOrd for Component {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(n) = self.as_segment() {
+            return write!(f, "seg={}", n);
+        }
+        if let Some(n) = self.as_version() {
+            return write!(f, "v={}", n);
+        }
+
+        if self.t == Type::GenericNameComponent as u64 {
+            // Components of only periods get three extra periods
+            if self.value.iter().all(|b| *b == b'.') {
+                write!(f, "...")?;
+            }
+        } else if self.t == Type::ImplicitSha256DigestComponent as u64 {
+            write!(f, "sha256digest=")?;
+            return write_hex(f, &self.value);
+        } else if self.t == Type::ParametersSha256DigestComponent as u64 {
+            write!(f, "params-sha256=")?;
+            return write_hex(f, &self.value);
+        } else {
+            write!(f, "{}=", self.t)?;
+        }
+        write_escaped(f, &self.value)
+    }
+}
+
+impl FromStr for Component {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (t, v) = match s.split_once('=') {
+            Some(("sha256digest", v)) => (Type::ImplicitSha256DigestComponent as u64, read_hex(v)?),
+            Some(("params-sha256", v)) => (Type::ParametersSha256DigestComponent as u64, read_hex(v)?),
+            Some(("seg", v)) => {
+                return Ok(Component::segment(v.parse().map_err(|_| invalid("Invalid segment number"))?));
+            }
+            Some(("v", v)) => {
+                return Ok(Component::version(v.parse().map_err(|_| invalid("Invalid version number"))?));
+            }
+            Some((t, v)) if !t.is_empty() && t.bytes().all(|b| b.is_ascii_digit()) => {
+                (t.parse::<u64>().map_err(|_| invalid("Invalid component type"))?, read_escaped(v)?)
+            }
+            _ => (Type::GenericNameComponent as u64, read_escaped(s)?),
+        };
+
+        let mut value = v;
+        if t == Type::GenericNameComponent as u64 && value.iter().all(|b| *b == b'.') {
+            if value.len() < 3 {
+                return Err(invalid("Invalid name component"));
+            }
+            value.drain(..3);
+        }
+
+        check_type(t, value.len())?;
+        Ok(Component::new(t, value))
+    }
+}
+
+impl Name {
+    pub fn new() -> Name {
+        Name { components: Vec::new() }
+    }
+
+    /**
+     * Decode the value of a Name TLV
+     */
+    pub fn from_bytes(vec: &[u8]) -> Result<Name, std::io::Error> {
+        let mut components = Vec::new();
+        let mut o = 0;
+        while o < vec.len() {
+            let (c, len) = Component::decode(&vec[o..])?;
+            components.push(c);
+            o += len;
+        }
+        Ok(Name { components })
+    }
+
+    /**
+     * Encode to the value of a Name TLV
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut vec = Vec::new();
+        for c in &self.components {
+            c.encode(&mut vec);
+        }
+        vec
+    }
+
+    pub fn push(&mut self, c: Component) -> &mut Name {
+        self.components.push(c);
+        self
+    }
+
+    pub fn is_prefix_of(&self, other: &Name) -> bool {
+        other.components.starts_with(&self.components)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.components.is_empty() {
+            return write!(f, "/");
+        }
+        for c in &self.components {
+            write!(f, "/{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Name {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("ndn:").unwrap_or(s);
+        let s = s.strip_prefix("//").map_or(s, |s| s.find('/').map_or("", |i| &s[i..]));
+
+        let mut name = Name::new();
+        for c in s.split('/').filter(|c| !c.is_empty()) {
+            name.push(c.parse()?);
+        }
+        Ok(name)
+    }
+}
+
+/**
+ * Format an encoded name as an NDN URI for logging
+ */
+pub fn uri(name: &[u8]) -> String {
+    match Name::from_bytes(name) {
+        Ok(name) => name.to_string(),
+        Err(_) => format!("<invalid name {:?}>", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_round_trip() {
+        for uri in [
+            "/",
+            "/a/b/c",
+            "/ndn/edu/ucla/seg=3",
+            "/video/v=1700000000000/seg=0",
+            "/a/32=keyword",
+            "/a/%00%FF/hello%20world",
+            "/..../...",
+            "/a/sha256digest=0000000000000000000000000000000000000000000000000000000000000000",
+            "/a/1000=value",
+            "/a/3=a",
+        ] {
+            let name: Name = uri.parse().unwrap();
+            assert_eq!(name.to_string(), uri);
+            assert_eq!(Name::from_bytes(&name.to_bytes()).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn parse_variants() {
+        let name: Name = "ndn:/a/b/".parse().unwrap();
+        assert_eq!(name.to_string(), "/a/b");
+        let name: Name = "ndn://authority/a".parse().unwrap();
+        assert_eq!(name.to_string(), "/a");
+        let name: Name = "/8=.../a=b".parse().unwrap();
+        assert_eq!(name.to_string(), "/.../a%3Db");
+
+        assert!("/..".parse::<Name>().is_err());
+        assert!("/a%G0".parse::<Name>().is_err());
+        assert!("/sha256digest=00".parse::<Name>().is_err());
+        assert!("/0=a".parse::<Name>().is_err());
+        assert!("/1=a".parse::<Name>().is_err());
+    }
+
+    #[test]
+    fn wire_encoding() {
+        let mut name = Name::new();
+        name.push(Component::generic(b"a")).push(Component::segment(256));
+        assert_eq!(name.to_bytes(), [0x08, 0x01, b'a', 0x32, 0x02, 0x01, 0x00]);
+        assert_eq!(name.components[1].as_segment(), Some(256));
+        assert_eq!(uri(&name.to_bytes()), "/a/seg=256");
+    }
+
+    #[test]
+    fn canonical_order() {
+        let names: Vec<Name> = ["/", "/a", "/a/b", "/a/seg=1", "/b", "/aa", "/3=a"]
+            .iter().map(|n| n.parse().unwrap()).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        let sorted: Vec<String> = sorted.iter().map(|n| n.to_string()).collect();
+        assert_eq!(sorted, ["/", "/3=a", "/a", "/a/b", "/a/seg=1", "/b", "/aa"]);
+
+        let name: Name = "/a/b".parse().unwrap();
+        assert!(Name::from_str("/a").unwrap().is_prefix_of(&name));
+        assert!(!Name::from_str("/b").unwrap().is_prefix_of(&name));
+    }
+}
@@ -0,0 +1,511 @@
+/*!
+ * Typed network layer packets (NDN Packet Format v0.3)
+ * Names are kept as the value of the Name TLV, i.e. the concatenated
+ * component TLVs, like everywhere else in the forwarder.
+ */
+
+use super::{Type, is_critical, vec_decode, vec_encode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyLocator {
+    Name(Vec<u8>),
+    KeyDigest(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureInfo {
+    pub signature_type: u64,
+    pub key_locator: Option<KeyLocator>,
+    pub nonce: Option<Vec<u8>>,
+    pub time: Option<u64>,
+    pub seq_num: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MetaInfo {
+    pub content_type: Option<u64>,
+    pub freshness_period: Option<u64>,
+    /** Encoded name component TLV */
+    pub final_block_id: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Interest {
+    pub name: Vec<u8>,
+    pub can_be_prefix: bool,
+    pub must_be_fresh: bool,
+    pub forwarding_hint: Vec<Vec<u8>>,
+    pub nonce: Option<u32>,
+    pub lifetime: Option<u64>,
+    pub hop_limit: Option<u8>,
+    /**
+     * The name must end with a ParametersSha256DigestComponent computed
+     * by the caller when parameters are present
+     */
+    pub app_parameters: Option<Vec<u8>>,
+    pub signature_info: Option<SignatureInfo>,
+    pub signature_value: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Data {
+    pub name: Vec<u8>,
+    pub meta_info: Option<MetaInfo>,
+    pub content: Option<Vec<u8>>,
+    pub signature_info: SignatureInfo,
+    pub signature_value: Vec<u8>,
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/**
+ * Read a single TLV of the expected type at the start of vec
+ * Returns the value and the total length of the TLV
+ */
+fn read_outer(vec: &[u8], t: Type) -> Result<(&[u8], usize), std::io::Error> {
+    let tlo = vec_decode::read_tlo(vec)?;
+    if tlo.t != t as u64 {
+        return Err(invalid("Unexpected TLV type"));
+    }
+    let end = tlo.o + tlo.l as usize;
+    if end > vec.len() {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Unexpected EOF"));
+    }
+    Ok((&vec[tlo.o..end], end))
+}
+
+/**
+ * Split a TLV value into its child TLVs as (type, value)
+ */
+fn read_children(vec: &[u8]) -> Result<Vec<(u64, &[u8])>, std::io::Error> {
+    let mut children = Vec::new();
+    let mut o = 0;
+    while o < vec.len() {
+        let tlo = vec_decode::read_tlo(&vec[o..])?;
+        let end = o + tlo.o + tlo.l as usize;
+        if end > vec.len() {
+            return Err(invalid("Incorrect TLV encoding"));
+        }
+        children.push((tlo.t, &vec[o+tlo.o..end]));
+        o = end;
+    }
+    Ok(children)
+}
+
+fn read_nni(v: &[u8]) -> Result<u64, std::io::Error> {
+    vec_decode::read_nni(v, v.len() as u64)
+}
+
+/**
+ * Decode the delegation names of a ForwardingHint
+ * Accepts both plain Names and the older Delegation encoding
+ */
+fn decode_forwarding_hint(v: &[u8]) -> Result<Vec<Vec<u8>>, std::io::Error> {
+    let mut names = Vec::new();
+    for (t, v) in read_children(v)? {
+        if t == Type::Name as u64 {
+            names.push(v.to_vec());
+        } else if t == Type::Delegation as u64 {
+            // Delegation = Preference Name
+            for (t, v) in read_children(v)? {
+                if t == Type::Name as u64 {
+                    names.push(v.to_vec());
+                }
+            }
+        }
+    }
+    Ok(names)
+}
+
+impl KeyLocator {
+    fn decode(v: &[u8]) -> Result<KeyLocator, std::io::Error> {
+        match read_children(v)?.first() {
+            Some((t, v)) if *t == Type::Name as u64 => Ok(KeyLocator::Name(v.to_vec())),
+            Some((t, v)) if *t == Type::KeyDigest as u64 => Ok(KeyLocator::KeyDigest(v.to_vec())),
+            _ => Err(invalid("Invalid KeyLocator")),
+        }
+    }
+
+    fn encode(&self, vec: &mut Vec<u8>) {
+        let mut inner = Vec::new();
+        match self {
+            KeyLocator::Name(name) => vec_encode::write_tlv(&mut inner, Type::Name as u64, name),
+            KeyLocator::KeyDigest(digest) => vec_encode::write_tlv(&mut inner, Type::KeyDigest as u64, digest),
+        }
+        vec_encode::write_tlv(vec, Type::KeyLocator as u64, &inner);
+    }
+}
+
+impl SignatureInfo {
+    pub fn new(signature_type: u64) -> SignatureInfo {
+        SignatureInfo {
+            signature_type,
+            key_locator: None,
+            nonce: None,
+            time: None,
+            seq_num: None,
+        }
+    }
+
+    fn decode(v: &[u8]) -> Result<SignatureInfo, std::io::Error> {
+        let mut children = read_children(v)?.into_iter();
+        let mut info = match children.next() {
+            Some((t, v)) if t == Type::SignatureType as u64 => SignatureInfo::new(read_nni(v)?),
+            _ => { return Err(invalid("First TLV is not a SignatureType")); }
+        };
+
+        for (t, v) in children {
+            if t == Type::KeyLocator as u64 {
+                info.key_locator = Some(KeyLocator::decode(v)?);
+            } else if t == Type::SignatureNonce as u64 {
+                info.nonce = Some(v.to_vec());
+            } else if t == Type::SignatureTime as u64 {
+                info.time = Some(read_nni(v)?);
+            } else if t == Type::SignatureSeqNum as u64 {
+                info.seq_num = Some(read_nni(v)?);
+            } else if is_critical(t) {
+                return Err(invalid("Unrecognized critical TLV"));
+            }
+        }
+        Ok(info)
+    }
+
+    fn encode(&self, vec: &mut Vec<u8>, t: Type) {
+        let mut inner = Vec::new();
+        vec_encode::write_nni(&mut inner, Type::SignatureType as u64, self.signature_type);
+        if let Some(key_locator) = &self.key_locator {
+            key_locator.encode(&mut inner);
+        }
+        if let Some(nonce) = &self.nonce {
+            vec_encode::write_tlv(&mut inner, Type::SignatureNonce as u64, nonce);
+        }
+        if let Some(time) = self.time {
+            vec_encode::write_nni(&mut inner, Type::SignatureTime as u64, time);
+        }
+        if let Some(seq_num) = self.seq_num {
+            vec_encode::write_nni(&mut inner, Type::SignatureSeqNum as u64, seq_num);
+        }
+        vec_encode::write_tlv(vec, t as u64, &inner);
+    }
+}
+
+impl MetaInfo {
+    fn decode(v: &[u8]) -> Result<MetaInfo, std::io::Error> {
+        let mut info = MetaInfo::default();
+        for (t, v) in read_children(v)? {
+            if t == Type::ContentType as u64 {
+                info.content_type = Some(read_nni(v)?);
+            } else if t == Type::FreshnessPeriod as u64 {
+                info.freshness_period = Some(read_nni(v)?);
+            } else if t == Type::FinalBlockId as u64 {
+                info.final_block_id = Some(v.to_vec());
+            } else if is_critical(t) {
+                return Err(invalid("Unrecognized critical TLV"));
+            }
+        }
+        Ok(info)
+    }
+
+    fn encode(&self, vec: &mut Vec<u8>) {
+        let mut inner = Vec::new();
+        if let Some(content_type) = self.content_type {
+            vec_encode::write_nni(&mut inner, Type::ContentType as u64, content_type);
+        }
+        if let Some(freshness_period) = self.freshness_period {
+            vec_encode::write_nni(&mut inner, Type::FreshnessPeriod as u64, freshness_period);
+        }
+        if let Some(final_block_id) = &self.final_block_id {
+            vec_encode::write_tlv(&mut inner, Type::FinalBlockId as u64, final_block_id);
+        }
+        vec_encode::write_tlv(vec, Type::MetaInfo as u64, &inner);
+    }
+}
+
+impl Interest {
+    pub fn new(name: Vec<u8>) -> Interest {
+        Interest { name, ..Default::default() }
+    }
+
+    /**
+     * Decode an Interest TLV at the start of vec
+     * Returns the Interest and the length of its encoding
+     */
+    pub fn decode(vec: &[u8]) -> Result<(Interest, usize), std::io::Error> {
+        let (value, len) = read_outer(vec, Type::Interest)?;
+        let mut children = read_children(value)?.into_iter();
+
+        let mut interest = match children.next() {
+            Some((t, v)) if t == Type::Name as u64 => Interest::new(v.to_vec()),
+            _ => { return Err(invalid("First TLV is not a Name")); }
+        };
+
+        for (t, v) in children {
+            if t == Type::CanBePrefix as u64 {
+                interest.can_be_prefix = true;
+            } else if t == Type::MustBeFresh as u64 {
+                interest.must_be_fresh = true;
+            } else if t == Type::ForwardingHint as u64 {
+                interest.forwarding_hint = decode_forwarding_hint(v)?;
+            } else if t == Type::Nonce as u64 {
+                interest.nonce = Some(vec_decode::read_u32(v)?);
+            } else if t == Type::InterestLifetime as u64 {
+                interest.lifetime = Some(read_nni(v)?);
+            } else if t == Type::HopLimit as u64 {
+                interest.hop_limit = Some(vec_decode::read_u8(v)?);
+            } else if t == Type::ApplicationParameters as u64 {
+                interest.app_parameters = Some(v.to_vec());
+            } else if t == Type::InterestSignatureInfo as u64 {
+                interest.signature_info = Some(SignatureInfo::decode(v)?);
+            } else if t == Type::InterestSignatureValue as u64 {
+                interest.signature_value = Some(v.to_vec());
+            } else if is_critical(t) {
+                return Err(invalid("Unrecognized critical TLV"));
+            }
+        }
+
+        Ok((interest, len))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut inner = Vec::new();
+        vec_encode::write_tlv(&mut inner, Type::Name as u64, &self.name);
+        if self.can_be_prefix {
+            vec_encode::write_tlv(&mut inner, Type::CanBePrefix as u64, &[]);
+        }
+        if self.must_be_fresh {
+            vec_encode::write_tlv(&mut inner, Type::MustBeFresh as u64, &[]);
+        }
+        if !self.forwarding_hint.is_empty() {
+            let mut hint = Vec::new();
+            for name in &self.forwarding_hint {
+                vec_encode::write_tlv(&mut hint, Type::Name as u64, name);
+            }
+            vec_encode::write_tlv(&mut inner, Type::ForwardingHint as u64, &hint);
+        }
+        if let Some(nonce) = self.nonce {
+            vec_encode::write_tlv(&mut inner, Type::Nonce as u64, &nonce.to_be_bytes());
+        }
+        if let Some(lifetime) = self.lifetime {
+            vec_encode::write_nni(&mut inner, Type::InterestLifetime as u64, lifetime);
+        }
+        if let Some(hop_limit) = self.hop_limit {
+            vec_encode::write_tlv(&mut inner, Type::HopLimit as u64, &[hop_limit]);
+        }
+        if let Some(app_parameters) = &self.app_parameters {
+            vec_encode::write_tlv(&mut inner, Type::ApplicationParameters as u64, app_parameters);
+            if let Some(signature_info) = &self.signature_info {
+                signature_info.encode(&mut inner, Type::InterestSignatureInfo);
+            }
+            if let Some(signature_value) = &self.signature_value {
+                vec_encode::write_tlv(&mut inner, Type::InterestSignatureValue as u64, signature_value);
+            }
+        }
+
+        let mut out = Vec::new();
+        vec_encode::write_tlv(&mut out, Type::Interest as u64, &inner);
+        out
+    }
+}
+
+impl Data {
+    pub fn new(name: Vec<u8>) -> Data {
+        Data {
+            name,
+            meta_info: None,
+            content: None,
+            signature_info: SignatureInfo::new(0),
+            signature_value: Vec::new(),
+        }
+    }
+
+    /**
+     * Decode a Data TLV at the start of vec
+     * Returns the Data and the length of its encoding
+     */
+    pub fn decode(vec: &[u8]) -> Result<(Data, usize), std::io::Error> {
+        let (value, len) = read_outer(vec, Type::Data)?;
+        let mut children = read_children(value)?.into_iter();
+
+        let mut data = match children.next() {
+            Some((t, v)) if t == Type::Name as u64 => Data::new(v.to_vec()),
+            _ => { return Err(invalid("First TLV is not a Name")); }
+        };
+
+        let mut has_signature = false;
+        for (t, v) in children {
+            if t == Type::MetaInfo as u64 {
+                data.meta_info = Some(MetaInfo::decode(v)?);
+            } else if t == Type::Content as u64 {
+                data.content = Some(v.to_vec());
+            } else if t == Type::SignatureInfo as u64 {
+                data.signature_info = SignatureInfo::decode(v)?;
+                has_signature = true;
+            } else if t == Type::SignatureValue as u64 {
+                data.signature_value = v.to_vec();
+            } else if is_critical(t) {
+                return Err(invalid("Unrecognized critical TLV"));
+            }
+        }
+
+        if !has_signature {
+            return Err(invalid("Data without SignatureInfo"));
+        }
+        Ok((data, len))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut inner = Vec::new();
+        vec_encode::write_tlv(&mut inner, Type::Name as u64, &self.name);
+        if let Some(meta_info) = &self.meta_info {
+            meta_info.encode(&mut inner);
+        }
+        if let Some(content) = &self.content {
+            vec_encode::write_tlv(&mut inner, Type::Content as u64, content);
+        }
+        self.signature_info.encode(&mut inner, Type::SignatureInfo);
+        vec_encode::write_tlv(&mut inner, Type::SignatureValue as u64, &self.signature_value);
+
+        let mut out = Vec::new();
+        vec_encode::write_tlv(&mut out, Type::Data as u64, &inner);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(comps: &[&str]) -> Vec<u8> {
+        let mut name = Vec::new();
+        for c in comps {
+            vec_encode::write_tlv(&mut name, Type::GenericNameComponent as u64, c.as_bytes());
+        }
+        name
+    }
+
+    #[test]
+    fn interest_round_trip() {
+        let mut interest = Interest::new(name(&["ndn", "test"]));
+        interest.can_be_prefix = true;
+        interest.must_be_fresh = true;
+        interest.forwarding_hint = vec![name(&["isp"]), name(&["campus", "gw"])];
+        interest.nonce = Some(0xdeadbeef);
+        interest.lifetime = Some(10000);
+        interest.hop_limit = Some(32);
+
+        let wire = interest.encode();
+        let (decoded, len) = Interest::decode(&wire).unwrap();
+        assert_eq!(len, wire.len());
+        assert_eq!(decoded, interest);
+        assert_eq!(decoded.encode(), wire);
+    }
+
+    #[test]
+    fn forwarding_hint_delegation() {
+        // ForwardingHint { Delegation { Preference 1, Name /isp }, Name /campus }
+        let mut wire = vec![0x05, 0x1a, 0x07, 0x03, 0x08, 0x01, b'a', 0x1e, 0x13];
+        wire.extend_from_slice(&[0x1f, 0x0a, 0x1e, 0x01, 0x01, 0x07, 0x05, 0x08, 0x03, b'i', b's', b'p']);
+        wire.extend_from_slice(&[0x07, 0x05, 0x08, 0x03, b'c', b'a', b'm']);
+        let (interest, _) = Interest::decode(&wire).unwrap();
+        assert_eq!(interest.forwarding_hint, vec![name(&["isp"]), name(&["cam"])]);
+    }
+
+    #[test]
+    fn interest_minimal() {
+        let interest = Interest::new(name(&["a"]));
+        let wire = interest.encode();
+        assert_eq!(wire, [0x05, 0x05, 0x07, 0x03, 0x08, 0x01, b'a']);
+        assert_eq!(Interest::decode(&wire).unwrap().0, interest);
+    }
+
+    #[test]
+    fn signed_interest_round_trip() {
+        let mut info = SignatureInfo::new(3);
+        info.key_locator = Some(KeyLocator::Name(name(&["key"])));
+        info.nonce = Some(vec![1, 2, 3, 4]);
+        info.time = Some(1_700_000_000_000);
+        info.seq_num = Some(7);
+
+        let mut interest = Interest::new(name(&["cmd"]));
+        interest.nonce = Some(1);
+        interest.app_parameters = Some(vec![0xaa; 40]);
+        interest.signature_info = Some(info);
+        interest.signature_value = Some(vec![0x55; 64]);
+
+        let wire = interest.encode();
+        assert_eq!(Interest::decode(&wire).unwrap().0, interest);
+    }
+
+    #[test]
+    fn data_round_trip() {
+        let mut data = Data::new(name(&["ndn", "data", "v1"]));
+        data.meta_info = Some(MetaInfo {
+            content_type: Some(0),
+            freshness_period: Some(4000),
+            final_block_id: Some(name(&["v1"])),
+        });
+        data.content = Some(b"hello world".to_vec());
+        data.signature_info = SignatureInfo::new(0);
+        data.signature_info.key_locator = Some(KeyLocator::KeyDigest(vec![9; 32]));
+        data.signature_value = vec![0x42; 32];
+
+        let wire = data.encode();
+        let (decoded, len) = Data::decode(&wire).unwrap();
+        assert_eq!(len, wire.len());
+        assert_eq!(decoded, data);
+        assert_eq!(decoded.encode(), wire);
+    }
+
+    #[test]
+    fn decode_ignores_trailing_bytes() {
+        let mut wire = Data::new(name(&["x"])).encode();
+        let len = wire.len();
+        wire.extend_from_slice(&[0x05, 0x00]);
+        assert_eq!(Data::decode(&wire).unwrap().1, len);
+    }
+
+    #[test]
+    fn unrecognized_types() {
+        let wire = Interest::new(name(&["x"])).encode();
+
+        // Non-critical type is ignored
+        let mut inner = wire[2..].to_vec();
+        vec_encode::write_tlv(&mut inner, 200, &[1]);
+        let mut extended = Vec::new();
+        vec_encode::write_tlv(&mut extended, Type::Interest as u64, &inner);
+        assert_eq!(Interest::decode(&extended).unwrap().0, Interest::new(name(&["x"])));
+
+        // Critical type makes the packet invalid
+        let mut inner = wire[2..].to_vec();
+        vec_encode::write_tlv(&mut inner, 201, &[1]);
+        let mut extended = Vec::new();
+        vec_encode::write_tlv(&mut extended, Type::Interest as u64, &inner);
+        assert!(Interest::decode(&extended).is_err());
+
+        // Known types out of place are unrecognized
+        let mut inner = wire[2..].to_vec();
+        vec_encode::write_tlv(&mut inner, Type::Content as u64, &[1]);
+        let mut extended = Vec::new();
+        vec_encode::write_tlv(&mut extended, Type::Interest as u64, &inner);
+        assert!(Interest::decode(&extended).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_bad_packets() {
+        // Data without SignatureInfo
+        let mut inner = Vec::new();
+        vec_encode::write_tlv(&mut inner, Type::Name as u64, &name(&["x"]));
+        let mut wire = Vec::new();
+        vec_encode::write_tlv(&mut wire, Type::Data as u64, &inner);
+        assert!(Data::decode(&wire).is_err());
+
+        // Truncated Interest
+        let wire = Interest::new(name(&["x"])).encode();
+        assert!(Interest::decode(&wire[..wire.len() - 1]).is_err());
+
+        // Wrong outer type
+        assert!(Interest::decode(&Data::new(name(&["x"])).encode()).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::Read;
+
+// Largest TLV accepted from a stream, as MAX_NDN_PACKET_SIZE
+const MAX_TLV_LENGTH: usize = 8800;
+
+/*
+ * If the first octet is less than or equal to 252 (0xFC), the number is encoded in that octet.
+ * If the first octet is 253 (0xFD), the number is encoded in the following 2 octets, in network byte-order. This number must be greater than 252 (0xFC).
+ * If the first octet is 254 (0xFE), the number is encoded in the following 4 octets, in network byte-order. This number must be greater than 65535 (0xFFFF).
+ * If the first octet is 255 (0xFF), the number is encoded in the following 8 octets, in network byte-order. This number must be greater than 4294967295 (0xFFFFFFFF).
+*/
+// Returns None if buf does not hold the whole number yet
+fn decode_varnumber(buf: &[u8]) -> Option<(u64, usize)> {
+    let first = *buf.first()?;
+    let len = match first {
+        0..=252 => return Some((u64::from(first), 1)),
+        253 => 3,
+        254 => 5,
+        255 => 9,
+    };
+    if buf.len() < len {
+        return None;
+    }
+
+    let mut n = 0u64;
+    for b in &buf[1..len] {
+        n = (n << 8) | u64::from(*b);
+    }
+    Some((n, len))
+}
+
+/**
+ * Incremental TLV decoder for streams
+ * Bytes are buffered until a whole TLV has arrived, so reads that time out
+ * or return short never lose the framing
+ */
+#[derive(Default)]
+pub struct TlvDecoder {
+    buf: Vec<u8>,
+}
+
+impl TlvDecoder {
+    pub fn new() -> TlvDecoder {
+        TlvDecoder { buf: Vec::new() }
+    }
+
+    /**
+     * Read available bytes from the stream into the buffer
+     * Returns the number of bytes read, 0 at end of stream
+     */
+    pub fn read_from(&mut self, stream: &mut impl Read) -> Result<usize, std::io::Error> {
+        let mut chunk = [0u8; MAX_TLV_LENGTH];
+        let n = stream.read(&mut chunk)?;
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(n)
+    }
+
+    /**
+     * Take the next whole TLV from the buffer, if any, with its header
+     * Oversized TLVs are an error since the stream cannot be resynchronized
+     */
+    pub fn pop_tlv(&mut self) -> Result<Option<Vec<u8>>, std::io::Error> {
+        let (_, t_len) = match decode_varnumber(&self.buf) {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let (l, l_len) = match decode_varnumber(&self.buf[t_len..]) {
+            Some(l) => l,
+            None => return Ok(None),
+        };
+        if l > MAX_TLV_LENGTH as u64 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "TLV too long"));
+        }
+
+        let end = t_len + l_len + l as usize;
+        if self.buf.len() < end {
+            return Ok(None);
+        }
+
+        Ok(Some(self.buf.drain(..end).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // Returns one chunk per read, as a stream delivering partial writes
+    struct Chunks(VecDeque<Vec<u8>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+            let chunk = self.0.pop_front().unwrap_or_default();
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    fn decode(chunks: &[&[u8]]) -> Result<Vec<Vec<u8>>, std::io::Error> {
+        let mut stream = Chunks(chunks.iter().map(|c| c.to_vec()).collect());
+        let mut decoder = TlvDecoder::new();
+        let mut tlvs = Vec::new();
+        while decoder.read_from(&mut stream)? > 0 {
+            while let Some(tlv) = decoder.pop_tlv()? {
+                tlvs.push(tlv);
+            }
+        }
+        Ok(tlvs)
+    }
+
+    fn tlv(t: u8, len: usize) -> Vec<u8> {
+        let mut data = vec![t];
+        if len < 253 {
+            data.push(len as u8);
+        } else {
+            data.push(253);
+            data.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        data.extend((0..len).map(|i| i as u8));
+        data
+    }
+
+    #[test]
+    fn split_value() {
+        let packet = tlv(5, 300);
+        let tlvs = decode(&[&packet[..10], &packet[10..200], &packet[200..]]).unwrap();
+        assert_eq!(tlvs, vec![packet]);
+    }
+
+    #[test]
+    fn split_varnumber() {
+        // The read ends inside the 3-octet length
+        let packet = tlv(6, 1000);
+        let tlvs = decode(&[&packet[..2], &packet[2..3], &packet[3..]]).unwrap();
+        assert_eq!(tlvs, vec![packet]);
+    }
+
+    #[test]
+    fn several_per_read() {
+        let packets = [tlv(5, 10), tlv(6, 300), tlv(100, 0)];
+        let tlvs = decode(&[&packets.concat()]).unwrap();
+        assert_eq!(tlvs, packets);
+    }
+
+    #[test]
+    fn oversize_length() {
+        let mut packet = vec![5, 254];
+        packet.extend_from_slice(&(MAX_TLV_LENGTH as u32 + 1).to_be_bytes());
+        packet.extend_from_slice(&[0; 16]);
+        assert_eq!(decode(&[&packet]).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -25,7 +25,7 @@ pub fn read_varnumber(vec: &[u8]) -> Result<(u64, usize), std::io::Error> {
         }
         return Ok((u64::from(u64::from_be_bytes([
             vec[1], vec[2], vec[3], vec[4], vec[5], vec[6], vec[7], vec[8],
-        ])), 7));
+        ])), 9));
     }
 
     return Err(std::io::Error::new(
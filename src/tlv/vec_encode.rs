@@ -0,0 +1,71 @@
+use super::TLO;
+use super::varnumber::VarNumber;
+use super::vec_decode::read_tlo;
+use sha2::{Digest, Sha256};
+
+pub fn write_tlv(vec: &mut Vec<u8>, t: u64, value: &[u8]) {
+    vec.extend_from_slice(&VarNumber::from(t).to_bytes());
+    vec.extend_from_slice(&VarNumber::from(value.len()).to_bytes());
+    vec.extend_from_slice(value);
+}
+
+pub fn write_nni(vec: &mut Vec<u8>, t: u64, value: u64) {
+    if value <= u8::MAX as u64 {
+        write_tlv(vec, t, &(value as u8).to_be_bytes());
+    } else if value <= u16::MAX as u64 {
+        write_tlv(vec, t, &(value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        write_tlv(vec, t, &(value as u32).to_be_bytes());
+    } else {
+        write_tlv(vec, t, &value.to_be_bytes());
+    }
+}
+
+/**
+ * Re-encode a TLV with the value of its first child of type t replaced
+ * The child is appended if it is not present. The outer length is recomputed.
+ */
+pub fn replace_child(vec: &[u8], outer: &TLO, t: u64, value: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    let end = outer.o + outer.l as usize;
+    if end > vec.len() {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Unexpected EOF"));
+    }
+
+    let mut inner = Vec::with_capacity(outer.l as usize + value.len());
+    let mut replaced = false;
+    let mut o = outer.o;
+    while o < end {
+        let tlo = read_tlo(&vec[o..end])?;
+        let c_end = o + tlo.o + tlo.l as usize;
+        if c_end > end {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Incorrect TLV encoding"));
+        }
+
+        if tlo.t == t && !replaced {
+            write_tlv(&mut inner, t, value);
+            replaced = true;
+        } else {
+            inner.extend_from_slice(&vec[o..c_end]);
+        }
+        o = c_end;
+    }
+
+    if !replaced {
+        write_tlv(&mut inner, t, value);
+    }
+
+    let mut out = Vec::with_capacity(inner.len() + 18);
+    write_tlv(&mut out, outer.t, &inner);
+    Ok(out)
+}
+
+/**
+ * Encode the implicit SHA-256 digest component of a Data packet
+ * The digest is computed over the full Data wire encoding
+ */
+pub fn implicit_digest_component(data: &[u8]) -> Vec<u8> {
+    let digest = Sha256::digest(data);
+    let mut vec = Vec::with_capacity(digest.len() + 2);
+    write_tlv(&mut vec, super::Type::ImplicitSha256DigestComponent as u64, &digest);
+    vec
+}
@@ -1,17 +1,20 @@
 use std::net::SocketAddr;
 use std::mem::MaybeUninit;
-use std::io::{BufReader, IoSlice, IoSliceMut};
+use std::io::{IoSlice, IoSliceMut};
 use std::os::unix::net::{UnixStream,UnixListener};
 use std::os::unix::prelude::AsRawFd;
 use std::sync::Arc;
 use std::io::Write;
 
-use nix::sys::socket::{MsgFlags, RecvMmsgData, RecvMsg, SockaddrIn};
-mod stream_decode;
+use nix::sys::socket::{MsgFlags, RecvMmsgData, RecvMsg, SockaddrStorage};
+use rnfd::tlv::{self, stream_decode};
 
-fn handle_client(stream: UnixStream) {
+// Default rNFD UDP address, may be overridden by the first argument
+const RNFD_ADDR: &str = "127.0.0.1:7766";
+
+fn handle_client(stream: UnixStream, addr: SocketAddr) {
     // Start UDP socket to rNFD
-    let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::DGRAM, None).unwrap();
+    let socket = socket2::Socket::new(socket2::Domain::for_address(addr), socket2::Type::DGRAM, None).unwrap();
     socket.set_recv_buffer_size(10000 * 2000).unwrap();
     socket.set_send_buffer_size(10000 * 2000).unwrap();
     let socket_arc = Arc::new(socket);
@@ -24,7 +27,9 @@ fn handle_client(stream: UnixStream) {
     // Start thread to read from UDP socket and write to Unix socket
     std::thread::spawn(move || {
         let mut stream = &*stream_arc_clone;
-        let mut receive_buffers = [[0u8; 2000]; 100];
+        let mut truncated = 0u64;
+
+        let mut receive_buffers = vec![[0u8; tlv::MAX_NDN_PACKET_SIZE]; 100];
         let mut receive_buffers_addrs = [MaybeUninit::uninit(); 100];
         let mut receive_buffers_bytes = [0usize; 100];
 
@@ -42,7 +47,7 @@ fn handle_client(stream: UnixStream) {
                     })
                 }
 
-                let res: Result<Vec<RecvMsg<SockaddrIn>>, nix::errno::Errno>=
+                let res: Result<Vec<RecvMsg<SockaddrStorage>>, nix::errno::Errno>=
                     nix::sys::socket::recvmmsg(socket_arc_clone.as_raw_fd(), &mut msgs, MsgFlags::MSG_DONTWAIT, None);
                 match res {
                     Ok(vc) => {
@@ -56,6 +61,12 @@ fn handle_client(stream: UnixStream) {
                                 continue;
                             }
                             let addr = rr.address.unwrap();
+                            // Datagrams larger than the buffer are cut short
+                            if rr.flags.contains(MsgFlags::MSG_TRUNC) {
+                                truncated += 1;
+                                println!("Truncated datagram from rNFD, dropping ({} total)", truncated);
+                                continue;
+                            }
                             receive_buffers_addrs[i] = MaybeUninit::new(addr);
                             receive_buffers_bytes[i] = rr.bytes;
                         }
@@ -94,30 +105,42 @@ fn handle_client(stream: UnixStream) {
 
     // Start thread to read from unix socket and write to UDP socket
     stream_arc.set_read_timeout(Some(std::time::Duration::from_millis(1))).unwrap();
-    let mut stream = BufReader::with_capacity(8800*20, &*stream_arc);
-    let addr: SocketAddr = "127.0.0.1:7766".parse().unwrap();
+    let mut stream = &*stream_arc;
+    let mut decoder = stream_decode::TlvDecoder::new();
 
     let mut datas = vec![];
 
     loop {
         let mut should_send = false;
-        let res = stream_decode::read_tlv(&mut stream);
-        match res {
-            Ok(packet) => {
-                datas.push(packet.data);
+        match decoder.pop_tlv() {
+            Ok(Some(packet)) => {
+                datas.push(packet);
 
                 if datas.len() >= 10 {
                     should_send = true;
                 }
             }
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::WouldBlock {
-                    should_send = true;
-                } else {
-                    println!("Error reading from Unix socket: {:?}", e);
-                    return;
+            Ok(None) => {
+                match decoder.read_from(&mut stream) {
+                    Ok(0) => {
+                        println!("Unix socket closed");
+                        return;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        if e.kind() == std::io::ErrorKind::WouldBlock {
+                            should_send = true;
+                        } else {
+                            println!("Error reading from Unix socket: {:?}", e);
+                            return;
+                        }
+                    }
                 }
             }
+            Err(e) => {
+                println!("Error reading from Unix socket: {:?}", e);
+                return;
+            }
         }
 
         if should_send {
@@ -134,20 +157,10 @@ fn handle_client(stream: UnixStream) {
             }
 
             for i in 0..iovs.len() {
-                let s_addr;
-                match addr {
-                    SocketAddr::V4(v4addr) => {
-                        s_addr = nix::sys::socket::SockaddrIn::from(v4addr);
-                    },
-                    SocketAddr::V6(_) => {
-                        println!("IPv6 not supported yet");
-                        continue;
-                    }
-                }
                 msgs.push(nix::sys::socket::SendMmsgData {
                     iov: &iovs[i],
                     cmsgs: &[],
-                    addr: Some(s_addr),
+                    addr: Some(SockaddrStorage::from(addr)),
                     _lt: Default::default(),
                 });
             }
@@ -169,13 +182,18 @@ fn main() {
     let res = std::fs::remove_file(&path);
     if res.is_err() {}
 
+    let addr: SocketAddr = std::env::args().nth(1)
+        .unwrap_or(RNFD_ADDR.to_string())
+        .parse()
+        .unwrap();
+
     let listener = UnixListener::bind(&path).unwrap();
 
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
                 std::thread::spawn(move || {
-                    handle_client(stream);
+                    handle_client(stream, addr);
                 });
             }
             Err(err) => {
@@ -1 +0,0 @@
-pub mod stream_decode;
\ No newline at end of file
@@ -1,89 +1,135 @@
-use std::borrow::BorrowMut;
-use std::collections::HashMap;
-use std::io::{Write, BufReader};
-use std::os::unix::net::UnixStream;
-use std::{mem::MaybeUninit, os::unix::net::UnixListener};
-use std::net::SocketAddr;
-use std::sync::{Arc, RwLock};
-use crossbeam::channel::{Sender, Receiver};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixListener;
+use std::os::unix::prelude::AsRawFd;
+use std::sync::Arc;
+use crossbeam::deque::Injector;
 
+use crate::face::{Face, FaceId, FacePersistency, FaceScope, FaceTable, LinkType};
 use crate::socket::UdpPacket;
-use crate::unix_socket::stream_decode;
 
-pub fn listen_udp(
-    path: &str,
-    sender: Sender<Arc<UdpPacket>>,
-    receiver: Receiver<(Vec<u8>, SocketAddr)>
-) -> Result<(), std::io::Error> {
-    let path: String = "/tmp/rnfd.sock".to_string();
-    let res = std::fs::remove_file(&path);
-    if res.is_err() {}
+/**
+ * Face to a local application connected over the Unix socket
+ * The face lives as long as the connection
+ */
+pub struct UnixFace {
+    id: FaceId,
+    path: String,
+    fd: i32,
+    queue: Injector<Vec<u8>>,
+}
 
-    let listener = UnixListener::bind(&path).unwrap();
+impl Face for UnixFace {
+    fn id(&self) -> FaceId {
+        self.id
+    }
 
-    let socket_map: HashMap<u16, Arc<UnixStream>> = std::collections::HashMap::new();
-    let socket_map = Arc::new(RwLock::new(socket_map));
-    let sender = Arc::new(sender);
+    fn remote_uri(&self) -> String {
+        format!("fd://{}", self.fd)
+    }
 
-    thread_out(socket_map.clone(), receiver);
+    fn local_uri(&self) -> String {
+        format!("unix://{}", self.path)
+    }
 
-    let mut count = 1;
+    fn scope(&self) -> FaceScope {
+        FaceScope::Local
+    }
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let socket = Arc::new(stream);
-                socket_map.write().unwrap().insert(count, socket.clone());
-                thread_in(socket.clone(), sender.clone(), count);
-                count += 1;
-            }
-            Err(err) => {
-                println!("Error: {}", err);
-                break;
-            }
-        }
+    fn persistency(&self) -> FacePersistency {
+        FacePersistency::OnDemand
+    }
+
+    fn link_type(&self) -> LinkType {
+        LinkType::PointToPoint
+    }
+
+    fn mtu(&self) -> usize {
+        crate::tlv::MAX_NDN_PACKET_SIZE
+    }
+
+    fn send(&self, packet: Vec<u8>) {
+        self.queue.push(packet);
     }
-    Ok(())
 }
 
-fn thread_out(table: Arc<RwLock<HashMap<u16, Arc<UnixStream>>>>, receiver: Receiver<(Vec<u8>, SocketAddr)>) {
-    std::thread::spawn(move || {
-        loop {
-            let (data, addr) = receiver.recv().unwrap();
-            let t = table.read().unwrap();
-            let stream = t.get(&addr.port());
-            if stream.is_none() {
-                continue;
-            }
-            let mut stream = &**stream.unwrap();
-            let res = stream.write(&data);
-            if res.is_err() {
-                println!("Error writing to Unix socket");
-                table.write().unwrap().remove(&addr.port());
-                return;
-            }
-        }
-    });
+/**
+ * Remove a socket left over by a previous run
+ * Anything else at the path is left alone and reported
+ */
+fn remove_stale_socket(path: &str) -> Result<(), std::io::Error> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path),
+        Ok(_) => Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
 }
 
-fn thread_in(stream_arc: Arc<UnixStream>, sender: Arc<Sender<Arc<UdpPacket>>>, fake_port: u16) {
+pub fn listen_unix(
+    path: &str,
+    faces: Arc<FaceTable>,
+    sender: Arc<Injector<Arc<UdpPacket>>>,
+) -> Result<(), std::io::Error> {
+    println!("Starting Unix listener on {}", path);
+
+    remove_stale_socket(path)?;
+    let listener = UnixListener::bind(path)?;
+    let path = path.to_string();
+
     std::thread::spawn(move || {
-        let mut stream = BufReader::new(&*stream_arc);
-        loop {
-            let res = stream_decode::read_tlv(&mut stream);
-            match res {
-                Ok(packet) => {
-                    let packet = Arc::new(UdpPacket {
-                        data: packet.data,
-                        addr: SocketAddr::from(([0, 0, 0, 0], fake_port)),
-                    });
-                    sender.send(packet).unwrap();
-                }
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
                 Err(e) => {
-                    println!("DecodeError: {:?}", e);
-                    break;
+                    println!("Unix accept error: {:?}", e);
+                    continue;
                 }
+            };
+
+            let fd = stream.as_raw_fd();
+            let mut created = None;
+            faces.get_or_add(&format!("fd://{}", fd), |id| {
+                let face = Arc::new(UnixFace { id, path: path.clone(), fd, queue: Injector::new() });
+                created = Some(face.clone());
+                face
+            });
+
+            if let Some(face) = created {
+                let faces = faces.clone();
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    crate::stream::run_connection("Unix", face.id, &face.queue, &stream, &sender);
+                    // Remove the face before its fd is closed and reused
+                    faces.remove(face.id);
+                    drop(stream);
+                });
             }
         }
     });
-}
\ No newline at end of file
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_socket_removed_other_files_kept() {
+        let dir = std::env::temp_dir().join(format!("rnfd-usocket-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let sock = dir.join("stale.sock");
+        drop(UnixListener::bind(&sock).unwrap());
+        assert!(remove_stale_socket(sock.to_str().unwrap()).is_ok());
+        assert!(!sock.exists());
+        assert!(remove_stale_socket(sock.to_str().unwrap()).is_ok());
+
+        let file = dir.join("file");
+        std::fs::write(&file, b"data").unwrap();
+        assert!(remove_stale_socket(file.to_str().unwrap()).is_err());
+        assert!(file.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}